@@ -3,14 +3,38 @@
 #include "asr/AsrBackend.h"
 #include "asr/AsrBackendFactory.h"
 #include "audio/AudioCapture.h"
+#include "audio/DebugWavRecorder.h"
+#include "audio/SilenceGate.h"
 
 #include <QDateTime>
 #include <QDebug>
+#include <QJsonArray>
+#include <QJsonDocument>
+#include <QJsonObject>
+#include <algorithm>
 #include <cmath>
+#include <utility>
 
 using state::State;
 
-AsrController::AsrController(QObject *parent) : QObject(parent) {}
+namespace {
+constexpr int kStallCheckIntervalMs = 1000;
+constexpr int kDefaultStallTimeoutSecs = 5;
+constexpr int kMinGainDb = -20;
+constexpr int kMaxGainDb = 30;
+constexpr int kMaxRecentErrors = 10;
+// Audio kept for failOver(); the same bound as the backend's own
+// handshake buffer. Anything later is dropped from the copy.
+constexpr int kMaxPreConnectAudioMs = 10'000;
+} // namespace
+
+AsrController::AsrController(QObject *parent)
+    : QObject(parent), debugWav_(DebugWavRecorder::fromEnvironment()) {
+    stallTimer_.setInterval(kStallCheckIntervalMs);
+    connect(&stallTimer_, &QTimer::timeout, this, &AsrController::onStallCheck);
+    mergeTimer_.setSingleShot(true);
+    connect(&mergeTimer_, &QTimer::timeout, this, [this]() { flushMergedFinal(); });
+}
 AsrController::~AsrController() = default;
 
 bool AsrController::applyConfig(const OverlayConfig &cfg) {
@@ -20,16 +44,47 @@ bool AsrController::applyConfig(const OverlayConfig &cfg) {
     // from idle; CLI startup is naturally idle.
     if (currentState_ != State::Idle) return false;
 
-    removeTrailingPunctuation_ = cfg.removeTrailingPunctuation;
+    postProcess_ = postprocess::Pipeline::fromConfig(cfg);
+    spokenCommands_ = postprocess::CommandMap::fromConfig(cfg);
+    // [Asr] FinalMetadata: also emit transcriptFinalMeta.
+    finalMetadata_ = cfg.boolean(QStringLiteral("Asr"), QStringLiteral("FinalMetadata"), false);
+    // [Asr] MinFinalChars: drop finals with fewer letters/digits; 0 = off.
+    minFinalChars_ = cfg.intIn(QStringLiteral("Asr"), QStringLiteral("MinFinalChars"), 0, 0, 100);
+    // [Asr] MinFinalDurationMs: drop finals spanning less audio; 0 = off.
+    minFinalDurationMs_ =
+        cfg.intIn(QStringLiteral("Asr"), QStringLiteral("MinFinalDurationMs"), 0, 0, 10'000);
+    // [Asr] MergeWindowMs: merge finals closer together than this; 0 = off.
+    mergeWindowMs_ = cfg.intIn(QStringLiteral("Asr"), QStringLiteral("MergeWindowMs"), 0, 0, 2000);
+    mergeTimer_.setInterval(mergeWindowMs_);
 
     backend_ = asr::create(cfg, this);
     if (!backend_) return false;
+    if (const PcmFormat fmt = backend_->pcmFormat(); !AudioCapture::supports(fmt)) {
+        qWarning() << "AsrController: backend wants" << fmt.bitsPerSample << "bit,"
+                   << fmt.channels << "ch," << fmt.sampleRate
+                   << "Hz PCM; capture only produces 16-bit mono";
+        backend_.reset();
+        return false;
+    }
 
-    connect(backend_.get(), &AsrBackend::partial, this, &AsrController::onBackendPartial);
-    connect(backend_.get(), &AsrBackend::final_, this, &AsrController::onBackendFinal);
-    connect(backend_.get(), &AsrBackend::error, this, &AsrController::onBackendError);
-    connect(backend_.get(), &AsrBackend::connected, this, &AsrController::onBackendConnected);
-    connect(backend_.get(), &AsrBackend::finished, this, &AsrController::onBackendFinished);
+    connectBackend(backend_.get());
+
+    // [Asr] FallbackProfile: profile to fail over to before a session
+    // connects; it must use the same PCM format.
+    fallbackBackend_.reset();
+    fallbackConfig_.reset();
+    onFallback_ = false;
+    if (const QString name = cfg.str(QStringLiteral("Asr"), QStringLiteral("FallbackProfile"));
+        !name.isEmpty()) {
+        // Only resolved here; failOver() builds the backend if it's needed.
+        if (auto fbCfg = cfg.withProfile(name)) {
+            fallbackConfig_ = std::make_unique<OverlayConfig>(std::move(*fbCfg));
+            fallbackProfile_ = name;
+        } else {
+            qWarning() << "AsrController: [Asr] FallbackProfile" << name
+                       << "is unknown; no fallback";
+        }
+    }
 
     if (!audio_) {
         audio_ = std::make_unique<AudioCapture>(this);
@@ -45,16 +100,249 @@ bool AsrController::applyConfig(const OverlayConfig &cfg) {
                 &AsrController::onAudioError, Qt::QueuedConnection);
         connect(audio_.get(), &AudioCapture::warmedUp, this,
                 &AsrController::onAudioWarmedUp, Qt::QueuedConnection);
+        connect(audio_.get(), &AudioCapture::clipping, this,
+                &AsrController::onAudioClipping, Qt::QueuedConnection);
+    }
+    // [Audio] Device / Server: PA source and server; empty = defaults.
+    audio_->setDevice(cfg.str(QStringLiteral("Audio"), QStringLiteral("Device")));
+    audio_->setServer(cfg.str(QStringLiteral("Audio"), QStringLiteral("Server")));
+    audio_->setSimulatedSource(simulatedAudio_);
+    audio_->setFifoSource(qEnvironmentVariable("ANYTALK_AUDIO_FIFO"));
+    {
+        const QString raw = cfg.str(QStringLiteral("Audio"), QStringLiteral("Source"));
+        bool ok = true;
+        audio_->setSource(AudioCapture::sourceFromString(raw, &ok));
+        if (!ok) {
+            qWarning() << "AsrController: unknown [Audio] Source" << raw
+                       << "— expected mic | monitor; using mic";
+        }
+    }
+    // applyConfig only runs while idle, so a backend swap can't change the
+    // format under a live stream.
+    audio_->setFormat(backend_->pcmFormat());
+    {
+        const QString raw = cfg.str(QStringLiteral("Audio"), QStringLiteral("Downmix"));
+        bool ok = true;
+        audio_->setDownmix(AudioCapture::downmixFromString(raw, &ok));
+        if (!ok) {
+            qWarning() << "AsrController: unknown [Audio] Downmix" << raw
+                       << "— expected average | left | right | max | first | index:0|1;"
+                          " using average";
+        }
+    }
+    // [Audio] HighPass / HighPassHz: DC and rumble filter, off by default.
+    audio_->setHighPassHz(
+        cfg.boolean(QStringLiteral("Audio"), QStringLiteral("HighPass"), false)
+            ? cfg.intIn(QStringLiteral("Audio"), QStringLiteral("HighPassHz"), 80, 20, 500)
+            : 0.0);
+    // [Audio] InputGainDb: software gain.
+    setInputGainDb(cfg.intIn(QStringLiteral("Audio"), QStringLiteral("InputGainDb"), 0,
+                             kMinGainDb, kMaxGainDb));
+    // [Audio] StallTimeout: s without audio before the session fails. 0
+    // disables; anything under 2 s would trip during PA open + warm-up.
+    const int stallSecs = cfg.intIn(QStringLiteral("Audio"), QStringLiteral("StallTimeout"),
+                                    kDefaultStallTimeoutSecs, 0, 3600);
+    stallTimeoutMs_ = stallSecs == 0 ? 0 : std::max(stallSecs, 2) * 1000LL;
+    if (cfg.boolean(QStringLiteral("Audio"), QStringLiteral("TrimLeadingSilence"), false)) {
+        // Speech is TrimThresholdDb dBFS held for TrimMinSpeechMs.
+        const int db =
+            cfg.intIn(QStringLiteral("Audio"), QStringLiteral("TrimThresholdDb"), -45, -80, -10);
+        const int minMs =
+            cfg.intIn(QStringLiteral("Audio"), QStringLiteral("TrimMinSpeechMs"), 80, 20, 1000);
+        const PcmFormat fmt = backend_->pcmFormat();
+        // 300 ms of pre-roll covers the quiet onset of most initials.
+        silenceGate_ = std::make_unique<SilenceGate>(db, minMs, 300, fmt.bytesFor(1));
+        // Zero frames this often while gated keep the server session; 0 = off.
+        const int keepMs =
+            cfg.intIn(QStringLiteral("Audio"), QStringLiteral("TrimKeepaliveMs"), 1000, 0, 60'000);
+        keepaliveMs_ = keepMs == 0 ? 0 : std::max(keepMs, 200);
+    } else {
+        silenceGate_.reset();
+        keepaliveMs_ = 0;
+    }
+    activeProfile_ = cfg.activeProfile;
+    appliedConfigIni_ = cfg.toIni(/*redactSecrets=*/true);
+    return true;
+}
+
+void AsrController::connectBackend(AsrBackend *b) {
+    connect(b, &AsrBackend::partial, this, &AsrController::onBackendPartial);
+    connect(b, &AsrBackend::final_, this, &AsrController::onBackendFinal);
+    connect(b, &AsrBackend::finalTiming, this, &AsrController::onBackendFinalTiming);
+    connect(b, &AsrBackend::error, this, &AsrController::onBackendError);
+    connect(b, &AsrBackend::connected, this, &AsrController::onBackendConnected);
+    connect(b, &AsrBackend::finished, this, &AsrController::onBackendFinished);
+}
+
+bool AsrController::failOver(const QString &reason) {
+    if (!fallbackConfig_ || onFallback_ || wsConnected_ ||
+        currentState_ != State::Connecting) {
+        return false;
+    }
+    auto fb = asr::create(*fallbackConfig_, this);
+    if (!fb) {
+        qWarning() << "AsrController: fallback profile" << fallbackProfile_
+                   << "is not usable; no fallback";
+        return false;
+    }
+    if (!(fb->pcmFormat() == backend_->pcmFormat())) {
+        // Capture is already running in the primary's format.
+        qWarning() << "AsrController: fallback profile" << fallbackProfile_
+                   << "wants a different PCM format; no fallback";
+        return false;
+    }
+    qWarning().noquote() << "AsrController: primary backend failed before connecting ("
+                         << reason << ") — switching to fallback profile" << fallbackProfile_;
+    connectBackend(fb.get());
+    // The primary is parked in fallbackBackend_ (it is still inside the
+    // signal that got us here) and swapped back at the next start.
+    fallbackBackend_ = std::move(fb);
+    std::swap(backend_, fallbackBackend_);
+    onFallback_ = true;
+    emit warningOccurred(QStringLiteral("asr_fallback"));
+    backend_->start();
+    // The primary's handshake buffer died with it; hand the fallback the
+    // copy we kept so the user's first words still make it.
+    for (const auto &chunk : std::as_const(preConnectAudio_)) backend_->pushPcm(chunk);
+    preConnectAudio_.clear();
+    preConnectBytes_ = 0;
+    return true;
+}
+
+void AsrController::setInputGainDb(double db) {
+    if (!audio_) return;
+    const double clamped = std::clamp<double>(db, kMinGainDb, kMaxGainDb);
+    if (clamped != db) {
+        qWarning() << "AsrController: input gain" << db << "dB out of range; using" << clamped;
     }
+    audio_->setInputGainDb(clamped);
+}
+
+double AsrController::inputGainDb() const {
+    return audio_ ? audio_->inputGainDb() : 0.0;
+}
+
+bool AsrController::setAudioSource(const QString &name) {
+    if (!audio_ || currentState_ != State::Idle) return false;
+    bool ok = false;
+    const auto source = AudioCapture::sourceFromString(name, &ok);
+    if (!ok) return false;
+    audio_->setSource(source);
     return true;
 }
 
+void AsrController::injectTranscript(const QString &text, bool partial) {
+    if (!isRecording()) {
+        // A one-shot session of its own: nothing the last real session
+        // left behind (trim prefix, held finals, timing) applies to it, and
+        // it has no capture stats to report.
+        finalBuffer_.clear();
+        lastPartial_.clear();
+        lastRawPartial_.clear();
+        committedPrefix_.clear();
+        flushMergedFinal(/*deliver=*/false);
+        pendingFinalStartMs_ = pendingFinalEndMs_ = -1;
+        stats_ = {};
+    }
+    if (partial) {
+        onBackendPartial(text);
+        return;
+    }
+    onBackendFinal(text);
+    if (!isRecording()) enterIdle(/*fromError=*/false);
+}
+
+QString AsrController::languagesJson() const {
+    QJsonObject o;
+    o.insert(QStringLiteral("supported"),
+             QJsonArray::fromStringList(backend_ ? backend_->supportedLanguages() : QStringList()));
+    o.insert(QStringLiteral("selected"),
+             QJsonArray::fromStringList(backend_ ? backend_->languages() : QStringList()));
+    return QString::fromUtf8(QJsonDocument(o).toJson(QJsonDocument::Compact));
+}
+
+QStringList AsrController::setLanguages(const QStringList &langs) {
+    if (!backend_) return {};
+    const QStringList accepted = backend_->setLanguages(langs);
+    if (accepted.size() != langs.size()) {
+        qWarning() << "AsrController: languages" << langs << "— accepted" << accepted;
+    }
+    return accepted;
+}
+
+QString AsrController::audioInfoJson() const {
+    QJsonObject o;
+    if (audio_) {
+        const auto info = audio_->streamInfo();
+        o.insert(QStringLiteral("open"), info.open);
+        o.insert(QStringLiteral("source"), audio_->source() == AudioCapture::Source::Monitor
+                                               ? QStringLiteral("monitor")
+                                               : QStringLiteral("mic"));
+        o.insert(QStringLiteral("backend"), QStringLiteral("pulseaudio-simple"));
+        o.insert(QStringLiteral("server"),
+                 info.server.isEmpty() ? QStringLiteral("(default)") : info.server);
+        o.insert(QStringLiteral("device"),
+                 info.device.isEmpty() ? QStringLiteral("(default)") : info.device);
+        o.insert(QStringLiteral("sample_rate"), info.sampleRate);
+        o.insert(QStringLiteral("channels"), info.channels);
+        o.insert(QStringLiteral("sample_format"), info.sampleFormat);
+        o.insert(QStringLiteral("fragment_bytes"), info.fragmentBytes);
+        o.insert(QStringLiteral("downmix"), info.downmix);
+        // PA resamples to the requested rate server-side; there is no local
+        // resampler stage.
+        o.insert(QStringLiteral("resampler"), QStringLiteral("pulseaudio"));
+        o.insert(QStringLiteral("high_pass_hz"), info.highPassHz);
+        o.insert(QStringLiteral("input_gain_db"), audio_->inputGainDb());
+        o.insert(QStringLiteral("clip_ratio"), audio_->clipRatio());
+    }
+    return QString::fromUtf8(QJsonDocument(o).toJson(QJsonDocument::Compact));
+}
+
+QString AsrController::effectiveConfigJson() const {
+    QJsonObject o;
+    o.insert(QStringLiteral("config_file"), OverlayConfig::configFilePath());
+    o.insert(QStringLiteral("profile"), activeProfile_);
+    o.insert(QStringLiteral("config"), appliedConfigIni_);
+    QJsonArray env;
+    for (const char *name : {"ANYTALK_CONFIG", "ANYTALK_PROFILE", "ANYTALK_CA_BUNDLE",
+                             "ANYTALK_AUDIO_FIFO"}) {
+        if (qEnvironmentVariableIsSet(name)) env.append(QLatin1String(name));
+    }
+    o.insert(QStringLiteral("env_overrides"), env);
+    QJsonObject runtime;
+    runtime.insert(QStringLiteral("input_gain_db"), inputGainDb());
+    runtime.insert(QStringLiteral("languages"),
+                   QJsonArray::fromStringList(backend_ ? backend_->languages() : QStringList()));
+    if (audio_) {
+        runtime.insert(QStringLiteral("audio_source"),
+                       audio_->source() == AudioCapture::Source::Monitor
+                           ? QStringLiteral("monitor")
+                           : QStringLiteral("mic"));
+    }
+    o.insert(QStringLiteral("runtime"), runtime);
+    return QString::fromUtf8(QJsonDocument(o).toJson(QJsonDocument::Compact));
+}
+
+QString AsrController::lastErrorJson() const {
+    if (recentErrors_.isEmpty()) return QStringLiteral("{}");
+    const auto &e = recentErrors_.last();
+    QJsonObject o;
+    o.insert(QStringLiteral("ts"), e.at.toString(Qt::ISODateWithMs));
+    o.insert(QStringLiteral("message"), e.message);
+    o.insert(QStringLiteral("session"), static_cast<qint64>(e.session));
+    o.insert(QStringLiteral("recent"), static_cast<int>(recentErrors_.size()));
+    return QString::fromUtf8(QJsonDocument(o).toJson(QJsonDocument::Compact));
+}
+
+void AsrController::reportError(const QString &msg) {
+    sessionFailed_ = true;
+    recentErrors_.append({QDateTime::currentDateTime(), msg, session_});
+    if (recentErrors_.size() > kMaxRecentErrors) recentErrors_.removeFirst();
+    emit errorOccurred(msg);
+}
+
 QString AsrController::postProcess(const QString &text) const {
-    if (!removeTrailingPunctuation_) return text;
-    static const QString puncts = QStringLiteral("，。！？、；：,.!?;:");
-    QString out = text;
-    while (!out.isEmpty() && puncts.contains(out.back())) out.chop(1);
-    return out;
+    return postProcess_.apply(text);
 }
 
 // ---- Recording lifecycle ----
@@ -63,19 +351,47 @@ void AsrController::startRecording() {
     if (!backend_) {
         // Caller should have invoked applyConfig() and got false back; surface
         // for them so the overlay can pop the SettingsDialog.
-        emit errorOccurred(QStringLiteral("配置缺失，请先填写 AppID / AccessToken"));
+        reportError(QStringLiteral("配置缺失，请先填写 AppID / AccessToken"));
         emit stateChanged(state::Error);
         return;
     }
-    if (currentState_ == State::Recording ||
-        currentState_ == State::Connecting) {
-        return;
-    }
+    if (isRecording()) return;
     finalBuffer_.clear();
+    lastPartial_.clear();
+    lastRawPartial_.clear();
+    committedPrefix_.clear();
     wsConnected_ = false;
     audioWarmedUp_ = false;
+    if (silenceGate_) silenceGate_->reset();
+    lastBackendPush_.invalidate();
+    if (onFallback_) {
+        // Every session tries the primary first; the fallback is rebuilt
+        // if this one needs it too.
+        std::swap(backend_, fallbackBackend_);
+        fallbackBackend_.reset();
+        onFallback_ = false;
+    }
+    preConnectAudio_.clear();
+    preConnectBytes_ = 0;
+    stats_ = {};
+    stats_.active = true;
+    stats_.wall.start();
+    lastPcm_.start();
+    if (stallTimeoutMs_ > 0) stallTimer_.start();
+    flushing_ = false;
     currentState_ = State::Connecting;
     emit stateChanged(state::toString(currentState_));
+    if (debugWav_) debugWav_->begin(audio_->format().sampleRate);
+    // A cancel/flush just before this joined the old capture thread, but
+    // its last chunks may still be queued on our event loop. Everything
+    // posted before this barrier belongs to that session; the new thread
+    // can only post after it.
+    acceptPcm_ = false;
+    const quint64 session = ++session_;
+    sessionFailed_ = false;
+    QMetaObject::invokeMethod(this, [this, session]() {
+        if (session == session_) acceptPcm_ = true;
+    }, Qt::QueuedConnection);
     // Both return immediately; WS handshake, pa_simple_new(), and PA
     // warm-up all overlap. PA failure surfaces via onAudioError.
     backend_->start();
@@ -85,16 +401,44 @@ void AsrController::startRecording() {
 void AsrController::stopRecording() {
     if (currentState_ != State::Recording &&
         currentState_ != State::Connecting) return;
+    stallTimer_.stop();
+    stats_.mark(stats_.stopMs);
     if (audio_) audio_->stop();
-    if (backend_) backend_->stop();
+    // audio_->stop() joined the capture thread, so every pcm chunk it read
+    // is already queued on our event loop. Queue the backend stop behind
+    // them — calling it directly would flip the backend to Stopping first
+    // and the tail of the utterance would be discarded by pushPcm().
+    // If a cancel and a new start overtake it, the stop is stale: skip it.
+    QMetaObject::invokeMethod(this, [this, session = session_]() {
+        if (session != session_ || !backend_) return;
+        backend_->stop();
+    }, Qt::QueuedConnection);
+    // Non-streaming backends answer only after that stop; say so now rather
+    // than sit silently on "recording" until the final shows up.
+    if (backend_ && !backend_->streamsResults()) {
+        currentState_ = State::Processing;
+        emit stateChanged(state::toString(currentState_));
+    }
     // Don't enterIdle yet — the backend still needs to drain remaining
     // server-side finals after our LAST audio frame. enterIdle runs in
     // onBackendFinished, which fires after the WebSocket cleanly closes.
 }
 
+void AsrController::flushRecording() {
+    if (!isRecording()) return;
+    stats_.mark(stats_.stopMs);
+    if (audio_) audio_->stop();
+    if (!lastPartial_.isEmpty()) finalBuffer_ += postProcess(lastPartial_);
+    lastPartial_.clear();
+    // The cancel's finished() — synchronous today, but don't rely on it —
+    // must not run enterIdle a second time; we commit below instead.
+    flushing_ = true;
+    if (backend_) backend_->cancel();
+    enterIdle(/*fromError=*/false);
+}
+
 void AsrController::toggleRecording() {
-    if (currentState_ == State::Recording ||
-        currentState_ == State::Connecting) {
+    if (isRecording()) {
         stopRecording();
     } else {
         startRecording();
@@ -107,29 +451,105 @@ void AsrController::cancelRecording() {
     // Cancel discards: drop accumulated text, no commit. Going straight to
     // idle is correct here because we don't expect any further finals.
     finalBuffer_.clear();
+    flushMergedFinal(/*deliver=*/false);
     enterIdle(/*fromError=*/false);
     emit cancelled();
 }
 
 void AsrController::enterIdle(bool fromError) {
+    flushMergedFinal(/*deliver=*/!fromError);
+    reportSessionStats();
     currentState_ = State::Idle;
+    stallTimer_.stop();
+    if (debugWav_) debugWav_->finish();
     if (!fromError && !finalBuffer_.isEmpty()) {
         emit commitText(finalBuffer_);
     }
     finalBuffer_.clear();
+    lastPartial_.clear();
+    lastRawPartial_.clear();
+    committedPrefix_.clear();
     emit stateChanged(state::toString(currentState_));
 }
 
+void AsrController::reportSessionStats() {
+    if (!stats_.active) return;
+    stats_.active = false;
+    const int bytesPerMs = audio_ ? audio_->format().bytesFor(1) : 32;
+    const double audioSecs =
+        static_cast<double>(stats_.bytesSent) / std::max(bytesPerMs, 1) / 1000.0;
+    const int dropped = backend_ ? backend_->droppedChunks() : 0;
+    QJsonObject o;
+    o.insert(QStringLiteral("duration_ms"), stats_.wall.elapsed());
+    o.insert(QStringLiteral("chunks_captured"), stats_.chunksCaptured);
+    o.insert(QStringLiteral("chunks_sent"), stats_.chunksSent);
+    o.insert(QStringLiteral("audio_seconds"), std::round(audioSecs * 100.0) / 100.0);
+    o.insert(QStringLiteral("dropped_chunks"), dropped);
+    o.insert(QStringLiteral("peak_level"), std::round(stats_.peakLevel * 1000.0) / 1000.0);
+    o.insert(QStringLiteral("finals"), stats_.finals);
+    o.insert(QStringLiteral("fallback"), onFallback_);
+    // Latency phases, ms; -1 when the phase never happened.
+    QJsonObject latency;
+    latency.insert(QStringLiteral("connect_ms"), stats_.connectedMs);
+    latency.insert(QStringLiteral("first_audio_ms"), stats_.firstAudioMs);
+    latency.insert(QStringLiteral("recording_ms"), stats_.recordingMs);
+    latency.insert(QStringLiteral("first_partial_ms"), stats_.firstPartialMs);
+    latency.insert(QStringLiteral("first_final_ms"), stats_.firstFinalMs);
+    latency.insert(QStringLiteral("stop_to_last_final_ms"),
+                   stats_.stopMs >= 0 && stats_.lastFinalMs >= stats_.stopMs
+                       ? stats_.lastFinalMs - stats_.stopMs
+                       : -1);
+    o.insert(QStringLiteral("latency"), latency);
+    qInfo().noquote() << "AsrController: session stats" << stats_.chunksCaptured << "chunks captured,"
+                      << stats_.chunksSent << "sent (" << audioSecs << "s ), peak"
+                      << stats_.peakLevel << "," << dropped << "dropped," << stats_.finals
+                      << "finals; connect" << stats_.connectedMs << "ms, first partial"
+                      << stats_.firstPartialMs << "ms, stop→last final"
+                      << latency.value(QStringLiteral("stop_to_last_final_ms")).toInteger() << "ms";
+    emit sessionStats(QString::fromUtf8(QJsonDocument(o).toJson(QJsonDocument::Compact)));
+}
+
 // ---- Audio events ----
 
 void AsrController::onAudioPcm(const QByteArray &chunk) {
+    if (!acceptPcm_) return;  // tail of the previous session
+    lastPcm_.restart();
+    ++stats_.chunksCaptured;
     if (backend_ && currentState_ != State::Idle &&
         currentState_ != State::Error) {
-        backend_->pushPcm(chunk);
+        const QByteArray out = silenceGate_ ? silenceGate_->feed(chunk) : chunk;
+        if (out.isEmpty()) {
+            // Gated: only zeros go out, and only once the socket is up —
+            // before that pushPcm() would just fill the handshake buffer.
+            if (keepaliveMs_ > 0 && wsConnected_ &&
+                (!lastBackendPush_.isValid() || lastBackendPush_.elapsed() >= keepaliveMs_)) {
+                backend_->pushPcm(QByteArray(chunk.size(), '\0'));
+                lastBackendPush_.restart();
+            }
+            return;
+        }
+        lastBackendPush_.restart();
+        stats_.bytesSent += out.size();
+        // When the gate opens it releases its pre-roll in one go; keep the
+        // backend's frames chunk-sized.
+        for (qsizetype off = 0; off < out.size(); off += chunk.size()) {
+            const QByteArray piece = out.mid(off, chunk.size());
+            backend_->pushPcm(piece);
+            ++stats_.chunksSent;
+            stats_.mark(stats_.firstAudioMs);
+            if (fallbackConfig_ && !wsConnected_ &&
+                preConnectBytes_ + piece.size() <=
+                    backend_->pcmFormat().bytesFor(kMaxPreConnectAudioMs)) {
+                preConnectAudio_.append(piece);
+                preConnectBytes_ += piece.size();
+            }
+        }
+        if (debugWav_) debugWav_->append(out);
     }
 }
 
 void AsrController::onAudioLevel(double level) {
+    if (stats_.active) stats_.peakLevel = std::max(stats_.peakLevel, level);
     // Throttle to ~20 Hz and dedup identical buckets — without this every
     // 40 ms read re-broadcasts on D-Bus, including the long stretch of
     // 0.0 readings during silence that waybar observers don't care about.
@@ -143,24 +563,53 @@ void AsrController::onAudioLevel(double level) {
 }
 
 void AsrController::onAudioError(const QString &msg) {
+    stallTimer_.stop();
     // Recording state: drain via backend->stop() so any partials we have
     // become a final commit instead of being dropped on the floor.
     if (backend_ && currentState_ == State::Recording) {
         backend_->stop();
-        emit errorOccurred(msg);
+        reportError(msg);
         return;
     }
     finalBuffer_.clear();
+    flushMergedFinal(/*deliver=*/false);
     if (backend_) backend_->cancel();
-    emit errorOccurred(msg);
+    if (debugWav_) debugWav_->finish();
+    reportError(msg);
+    reportSessionStats();
     currentState_ = State::Error;
     emit stateChanged(state::toString(currentState_));
 }
 
+void AsrController::onStallCheck() {
+    if (currentState_ != State::Recording &&
+        currentState_ != State::Connecting) {
+        stallTimer_.stop();
+        return;
+    }
+    const qint64 silentMs = lastPcm_.elapsed();
+    if (stallTimeoutMs_ <= 0 || silentMs < stallTimeoutMs_) return;
+    qWarning() << "AsrController: no audio for" << silentMs << "ms; failing the session";
+    emit warningOccurred(QStringLiteral("audio_stalled"));
+    // No rebuild here: tearing down a wedged read thread blocks for up to
+    // AudioCapture's shutdown bound, which mid-session would freeze the UI.
+    // The next session opens a fresh stream anyway.
+    onAudioError(QStringLiteral("麦克风没有音频数据，请检查音频设备"));
+}
+
+void AsrController::onAudioClipping(double ratio) {
+    Q_UNUSED(ratio);
+    if (currentState_ != State::Recording) return;
+    emit warningOccurred(QStringLiteral("clipping"));
+}
+
 // ---- Backend events ----
 
 void AsrController::onBackendConnected() {
     wsConnected_ = true;
+    stats_.mark(stats_.connectedMs);
+    preConnectAudio_.clear();
+    preConnectBytes_ = 0;
     maybeEnterRecording();
 }
 
@@ -173,30 +622,160 @@ void AsrController::maybeEnterRecording() {
     if (currentState_ != State::Connecting) return;
     if (!wsConnected_ || !audioWarmedUp_) return;
     currentState_ = State::Recording;
+    stats_.mark(stats_.recordingMs);
     emit stateChanged(state::toString(currentState_));
 }
 
 void AsrController::onBackendPartial(const QString &text) {
-    emit transcriptPartial(text);
+    stats_.mark(stats_.firstPartialMs);
+    lastRawPartial_ = text;
+    lastPartial_ = stripCommitted(text);
+    emit transcriptPartial(lastPartial_);
+}
+
+bool AsrController::isNoiseFinal(const QString &text) const {
+    if (minFinalChars_ > 0) {
+        // Punctuation ITN hangs on a lone syllable doesn't make it speech.
+        const auto chars = std::count_if(text.cbegin(), text.cend(), [](QChar c) {
+            return c.isLetterOrNumber();
+        });
+        if (chars < minFinalChars_) return true;
+    }
+    if (minFinalDurationMs_ > 0 && pendingFinalStartMs_ >= 0 &&
+        pendingFinalEndMs_ >= pendingFinalStartMs_) {
+        if (pendingFinalEndMs_ - pendingFinalStartMs_ < minFinalDurationMs_) return true;
+    }
+    return false;
 }
 
-void AsrController::onBackendFinal(const QString &text) {
-    const QString processed = postProcess(text);
-    finalBuffer_ += processed;
-    emit transcriptFinal(processed);
+QString AsrController::stripCommitted(const QString &raw) const {
+    if (committedPrefix_.isEmpty()) return raw;
+    qsizetype n = 0;
+    const qsizetype limit = std::min(raw.size(), committedPrefix_.size());
+    while (n < limit && raw.at(n) == committedPrefix_.at(n)) ++n;
+    return raw.mid(n);
+}
+
+bool AsrController::commitPartial() {
+    if (currentState_ != State::Recording) return false;
+    const QString promoted =
+        lastPartial_.isEmpty()
+            ? QString()
+            : spokenCommands_.apply(postProcess(lastPartial_)).join(QString());
+    if (finalBuffer_.isEmpty() && promoted.isEmpty()) return false;
+    // Held finals first, so observers see them before the promoted one.
+    flushMergedFinal();
+    if (!lastPartial_.isEmpty()) {
+        // The server keeps sending this utterance from its start. Its raw
+        // text so far backs what was just promoted; trim that from the
+        // later partials and the final.
+        committedPrefix_ = lastRawPartial_;
+    }
+    if (!promoted.isEmpty()) emit transcriptFinal(promoted);
+    emit commitText(finalBuffer_ + promoted);
+    finalBuffer_.clear();
+    lastPartial_.clear();
+    return true;
+}
+
+void AsrController::onBackendFinal(const QString &rawText) {
+    const QString text = stripCommitted(rawText);
+    if (!committedPrefix_.isEmpty() && !rawText.startsWith(committedPrefix_)) {
+        qInfo() << "AsrController: server revised committed partial; trimming"
+                << rawText.size() - text.size() << "of" << committedPrefix_.size() << "chars";
+    }
+    committedPrefix_.clear();
+    lastRawPartial_.clear();
+    lastPartial_.clear();
+    if (text.isEmpty()) {
+        // All of it went out with commitPartial().
+        pendingFinalStartMs_ = pendingFinalEndMs_ = -1;
+        emit transcriptPartial(QString());
+        return;
+    }
+    if (isNoiseFinal(text)) {
+        qInfo() << "AsrController: dropping short final (" << text.size()
+                << "chars ) per [Asr] MinFinalChars / MinFinalDurationMs";
+        pendingFinalStartMs_ = pendingFinalEndMs_ = -1;
+        // The preedit was showing it as a partial; clear that too.
+        emit transcriptPartial(QString());
+        return;
+    }
+    ++stats_.finals;
+    stats_.mark(stats_.firstFinalMs);
+    if (stats_.active) stats_.lastFinalMs = stats_.wall.elapsed();
+    const QStringList pieces = spokenCommands_.apply(postProcess(text));
+    const qint64 startMs = std::exchange(pendingFinalStartMs_, -1);
+    const qint64 endMs = std::exchange(pendingFinalEndMs_, -1);
+    // Each spoken "commit" closes a piece: announce it, commit everything
+    // up to it now and keep going. TranscriptFinal carries every piece
+    // exactly once — the tail doesn't repeat what a commit delivered.
+    for (qsizetype i = 0; i + 1 < pieces.size(); ++i) {
+        if (!pieces.at(i).isEmpty()) holdFinal(pieces.at(i), startMs, endMs);
+        flushMergedFinal();
+        finalBuffer_ += pieces.at(i);
+        if (!finalBuffer_.isEmpty()) emit commitText(finalBuffer_);
+        finalBuffer_.clear();
+    }
+    finalBuffer_ += pieces.last();
+    if (pieces.size() > 1 && pieces.last().isEmpty()) return;
+    holdFinal(pieces.last(), startMs, endMs);
+    // Each final restarts the window, so the last one waits at most
+    // MergeWindowMs; a session end flushes sooner.
+    if (mergeWindowMs_ > 0) mergeTimer_.start();
+    else flushMergedFinal();
+}
+
+void AsrController::holdFinal(const QString &text, qint64 startMs, qint64 endMs) {
+    if (!mergePending_) {
+        mergePending_ = true;
+        mergedStartMs_ = startMs;
+    }
+    mergedFinal_ += text;
+    mergedEndMs_ = endMs;
+}
+
+void AsrController::flushMergedFinal(bool deliver) {
+    mergeTimer_.stop();
+    if (!mergePending_) return;
+    const QString text = std::exchange(mergedFinal_, QString());
+    mergePending_ = false;
+    if (!deliver) return;
+    emit transcriptFinal(text);
+    if (finalMetadata_) {
+        emit transcriptFinalMeta(text, mergedStartMs_, mergedEndMs_,
+                                 static_cast<int>(text.size()));
+    }
+}
+
+void AsrController::onBackendFinalTiming(qint64 startMs, qint64 endMs) {
+    pendingFinalStartMs_ = startMs;
+    pendingFinalEndMs_ = endMs;
 }
 
 void AsrController::onBackendError(const QString &msg) {
+    if (failOver(msg)) return;
+    stallTimer_.stop();
     finalBuffer_.clear();
+    flushMergedFinal(/*deliver=*/false);
     if (audio_) audio_->stop();
-    emit errorOccurred(msg);
+    if (debugWav_) debugWav_->finish();
+    reportError(msg);
+    reportSessionStats();
     currentState_ = State::Error;
     emit stateChanged(state::toString(currentState_));
 }
 
 void AsrController::onBackendFinished() {
+    if (flushing_) {
+        // The cancel issued by flushRecording(); it already went idle.
+        flushing_ = false;
+        return;
+    }
     if (currentState_ == State::Idle ||
         currentState_ == State::Error) return;
     if (audio_) audio_->stop();
+    // A clean drain means whatever broke earlier works again.
+    if (!sessionFailed_) recentErrors_.clear();
     enterIdle(/*fromError=*/false);
 }
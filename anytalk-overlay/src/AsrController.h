@@ -1,12 +1,19 @@
 #pragma once
 #include "OverlayState.h"
+#include "PostProcess.h"
 
+#include <QDateTime>
+#include <QElapsedTimer>
+#include <QList>
 #include <QObject>
 #include <QString>
+#include <QTimer>
 #include <memory>
 
 class AsrBackend;
 class AudioCapture;
+class DebugWavRecorder;
+class SilenceGate;
 struct OverlayConfig;
 
 /// Wires AudioCapture (mic input) and an AsrBackend (transcription engine)
@@ -24,14 +31,83 @@ public:
     /// (missing credentials, unknown backend name).
     bool applyConfig(const OverlayConfig &cfg);
 
-    /// Best-effort post-processing applied to a final segment before
-    /// commit (e.g. trailing punctuation removal).
+    /// Feed sessions from a WAV (at the backend rate) instead of the microphone
+    /// (`--simulate-audio`). Call before applyConfig(); empty = live mic.
+    void setSimulatedAudio(const QString &wavPath) { simulatedAudio_ = wavPath; }
+
+    /// Post-processing pipeline applied to a final segment before commit
+    /// (see PostProcess.h for the configurable transforms).
     QString postProcess(const QString &text) const;
 
+    /// "mic" or "monitor" (system audio) for the next session; false on
+    /// an unknown name or while a session is active. Starts at
+    /// `[Audio] Source`.
+    bool setAudioSource(const QString &name);
+
+    /// Test hook (`--test-mode`): deliver `text` as if the backend had sent
+    /// it, through the same post-processing and signals. Outside a session
+    /// each call is a session of its own (no SessionStats): a final is
+    /// committed immediately.
+    void injectTranscript(const QString &text, bool partial);
+
+    /// `{"supported":[...],"selected":[...]}` for the active backend.
+    QString languagesJson() const;
+    /// Select recognition languages for subsequent sessions; returns the
+    /// accepted subset (unknown codes are dropped).
+    QStringList setLanguages(const QStringList &langs);
+
+    /// JSON object describing the capture path: the negotiated PA stream
+    /// (AudioCapture::StreamInfo) plus the live gain and clip ratio. For
+    /// bug reports via the AudioInfo D-Bus method.
+    QString audioInfoJson() const;
+
+    /// What the next session will run with, as JSON: config file path,
+    /// profile, the applied config as INI with secrets redacted, which
+    /// ANYTALK_* overrides are set (names only), and runtime settings
+    /// (gain, languages, audio source) that live outside the file.
+    QString effectiveConfigJson() const;
+
+    /// Newest of the last few errors as JSON {"ts","message","session",
+    /// "recent"} ("recent" = how many are kept), or "{}" when none. The
+    /// list empties once a later session drains cleanly (a flush or a
+    /// cancel doesn't count — the drain was abandoned), so a
+    /// client arriving late can tell whether dictation is still broken.
+    QString lastErrorJson() const;
+
+    /// A session is connecting, recording or processing.
+    bool isRecording() const {
+        return currentState_ == state::State::Connecting ||
+               currentState_ == state::State::Recording ||
+               currentState_ == state::State::Processing;
+    }
+
+    /// Config profile of the last successful applyConfig(); empty = base.
+    QString activeProfile() const { return activeProfile_; }
+
+    /// Software input gain; applies immediately, including mid-session.
+    /// Starts at `[Audio] InputGainDb` and lasts for this process — it is
+    /// not written back to the config file.
+    void setInputGainDb(double db);
+    double inputGainDb() const;
+
 public slots:
+    // Three ways to end a session:
+    //   stopRecording   — stop the mic, let the backend drain; commit once
+    //                     the server has delivered its last final.
+    //   flushRecording  — stop the mic and commit what we have *now*
+    //                     (finals + the partial on screen), abandoning the
+    //                     drain. Also cuts short a drain already in flight.
+    //   cancelRecording — stop everything and commit nothing.
     void startRecording();
     void stopRecording();
+    void flushRecording();
     void cancelRecording();
+    /// Commit the finals so far plus the partial on screen *without* ending
+    /// the session, then keep listening. The committed partial is trimmed
+    /// from the server's later partials and its final for that utterance,
+    /// so the text isn't committed twice. False outside Recording or when
+    /// there is nothing to commit.
+    bool commitPartial();
     /// Idempotent toggle for the dumb-forward fcitx5 addon: starts a new
     /// session if idle/error, otherwise stops the active one.
     void toggleRecording();
@@ -40,11 +116,22 @@ signals:
     /// Mirrors backend events for the UI / D-Bus surface.
     void transcriptPartial(const QString &text);
     void transcriptFinal(const QString &text);
+    /// Companion to transcriptFinal when `[Asr] FinalMetadata` is on:
+    /// the segment's audio range (ms, -1 if unknown) and character count.
+    void transcriptFinalMeta(const QString &text, qint64 startMs, qint64 endMs, int chars);
     void stateChanged(const QString &state); // idle / connecting / recording / error
     void audioLevel(double level);            // 0..1, ~25 Hz
     void errorOccurred(const QString &text);
+    /// Once per session, when it ends (commit, cancel or error): JSON with
+    /// what was captured and sent — see sessionStatsJson().
+    void sessionStats(const QString &json);
+    /// Non-fatal condition worth telling the user about; `kind` is a
+    /// stable keyword ("clipping", "audio_stalled").
+    void warningOccurred(const QString &kind);
 
-    /// Final accumulated transcript ready to be committed (one shot per session).
+    /// Accumulated transcript ready to be committed: once when the session
+    /// ends, plus once per commitPartial() or spoken `commit` command (see
+    /// postprocess::CommandMap); both keep the session running.
     void commitText(const QString &text);
     /// Cancellation completed (no commit, no error). Drives short-lived
     /// overlay's exit on Esc/cancel paths.
@@ -55,22 +142,91 @@ private:
     void onAudioLevel(double level);
     void onAudioError(const QString &msg);
     void onAudioWarmedUp();
+    void onAudioClipping(double ratio);
 
     void onBackendPartial(const QString &text);
     void onBackendFinal(const QString &text);
+    void onBackendFinalTiming(qint64 startMs, qint64 endMs);
     void onBackendConnected();
     void onBackendFinished();
     void onBackendError(const QString &msg);
 
+    void onStallCheck();
+
     void maybeEnterRecording();
     void enterIdle(bool fromError);
+    /// Log and emit sessionStats for the session that just ended; no-op
+    /// if already reported.
+    void reportSessionStats();
+    void connectBackend(AsrBackend *b);
+    /// Swap in the fallback backend for this session if the primary failed
+    /// before its connection came up. True if the session carries on.
+    bool failOver(const QString &reason);
+    /// A final below [Asr] MinFinalChars letters/digits, or shorter than
+    /// MinFinalDurationMs when the backend gave timing.
+    bool isNoiseFinal(const QString &text) const;
+    /// Record `msg` for lastErrorJson(), then emit errorOccurred.
+    void reportError(const QString &msg);
+    /// Queue `text` for the next flushMergedFinal(), extending the held
+    /// span's end (its start is kept from the first held final).
+    void holdFinal(const QString &text, qint64 startMs, qint64 endMs);
+    /// Emit transcriptFinal (+ Meta) for finals held by [Asr]
+    /// MergeWindowMs; with `deliver` false just drop them.
+    void flushMergedFinal(bool deliver = true);
+    /// `raw` minus the part commitPartial() already committed (their
+    /// common prefix; the server may have revised the tail since).
+    QString stripCommitted(const QString &raw) const;
 
     std::unique_ptr<AudioCapture> audio_;
     std::unique_ptr<AsrBackend> backend_;
+    // `[Asr] FallbackProfile`: the config of that profile. failOver()
+    // builds a backend from it for the rest of a session whose primary
+    // fails before connecting; while that runs, the primary is parked in
+    // fallbackBackend_ and swapped back at the next start.
+    std::unique_ptr<OverlayConfig> fallbackConfig_;
+    std::unique_ptr<AsrBackend> fallbackBackend_;
+    QString fallbackProfile_;
+    bool onFallback_ = false;
+    // Copy of what was pushed before the connection came up, for failOver();
+    // at most kMaxPreConnectAudioMs of it.
+    QList<QByteArray> preConnectAudio_;
+    qsizetype preConnectBytes_ = 0;
+    std::unique_ptr<DebugWavRecorder> debugWav_;  // null unless ANYTALK_DEBUG_WAV_DIR
+    std::unique_ptr<SilenceGate> silenceGate_;    // null unless [Audio] TrimLeadingSilence
+    // While the gate holds audio back, send a chunk of digital silence this
+    // often so the server doesn't drop an apparently idle session.
+    int keepaliveMs_ = 0;                         // [Audio] TrimKeepaliveMs; 0 = off
+    QElapsedTimer lastBackendPush_;
 
-    bool removeTrailingPunctuation_ = false;
+    postprocess::Pipeline postProcess_;
+    postprocess::CommandMap spokenCommands_;
+    QString activeProfile_;
+    QString appliedConfigIni_;  // last applyConfig(), toIni(redactSecrets=true)
+    bool finalMetadata_ = false;
+    int minFinalChars_ = 0;          // 0 = keep everything
+    int minFinalDurationMs_ = 0;     // ditto
+    // [Asr] MergeWindowMs: finals arriving closer together than this are
+    // announced as one transcriptFinal. Only the signal is merged —
+    // finalBuffer_ (what gets committed) is fed immediately either way.
+    int mergeWindowMs_ = 0;          // 0 = one signal per final
+    QTimer mergeTimer_;
+    QString mergedFinal_;
+    bool mergePending_ = false;
+    qint64 mergedStartMs_ = -1;
+    qint64 mergedEndMs_ = -1;
+    // Timing announced by the backend for the next final; reset after use.
+    qint64 pendingFinalStartMs_ = -1;
+    qint64 pendingFinalEndMs_ = -1;
+    QString simulatedAudio_;
     state::State currentState_ = state::State::Idle;
     QString finalBuffer_;
+    // Latest partial not yet superseded by a final; what flushRecording()
+    // promotes. Cleared on final, mirroring OverlayWindow's preedit.
+    QString lastPartial_;
+    // The backend's untrimmed text behind lastPartial_, and what
+    // commitPartial() already committed of the current utterance.
+    QString lastRawPartial_;
+    QString committedPrefix_;
     qint64 lastLevelEmitMs_ = 0;
     double lastEmittedLevel_ = -1.0;  // sentinel: never matches a [0,1] bucket
     // Recording = ws connected AND mic produced real audio. Both flags are
@@ -78,4 +234,50 @@ private:
     // maybeEnterRecording() once both are true.
     bool wsConnected_ = false;
     bool audioWarmedUp_ = false;
+    // False from startRecording() until a queued barrier runs: chunks the
+    // previous capture thread posted before it was joined sit ahead of the
+    // barrier and are dropped instead of leaking into the new session.
+    bool acceptPcm_ = false;
+    // Bumped by every startRecording(). Work queued on behalf of a session
+    // captures it and does nothing once a newer session has started — a
+    // Stop → Cancel → Start burst must not stop the new session.
+    quint64 session_ = 0;
+    // For lastErrorJson(): newest last, at most kMaxRecentErrors.
+    struct ErrorRecord {
+        QDateTime at;
+        QString message;
+        quint64 session = 0;
+    };
+    QList<ErrorRecord> recentErrors_;
+    bool sessionFailed_ = false;  // an error was reported since the last start
+    // Stall watchdog: PipeWire occasionally stops delivering reads without
+    // failing them, which would leave us "recording" silence forever.
+    QTimer stallTimer_;
+    QElapsedTimer lastPcm_;
+    qint64 stallTimeoutMs_ = 5000;  // [Audio] StallTimeout; 0 = watchdog off
+    // Per-session capture / send totals for sessionStats. Tells "no audio
+    // captured" apart from "audio sent, no transcript" in bug reports.
+    struct SessionStats {
+        bool active = false;      // started and not yet reported
+        qint64 chunksCaptured = 0;
+        qint64 chunksSent = 0;    // after the silence gate, keepalives excluded
+        qint64 bytesSent = 0;
+        double peakLevel = 0.0;   // raw RMS, 0..1
+        int finals = 0;
+        QElapsedTimer wall;
+        // Phase timestamps, ms since start; -1 = didn't happen.
+        qint64 connectedMs = -1;     // backend connected()
+        qint64 firstAudioMs = -1;    // first chunk handed to the backend
+        qint64 recordingMs = -1;     // state → recording (UI ready)
+        qint64 firstPartialMs = -1;
+        qint64 firstFinalMs = -1;
+        qint64 stopMs = -1;          // stop / flush requested
+        qint64 lastFinalMs = -1;
+        void mark(qint64 &at) const {
+            if (active && at < 0) at = wall.elapsed();
+        }
+    } stats_;
+    // flushRecording() cancelled the backend and committed itself; the
+    // backend's finished() for that cancel is to be ignored.
+    bool flushing_ = false;
 };
@@ -0,0 +1,104 @@
+#include "Bench.h"
+
+#include "AsrController.h"
+#include "audio/Wav.h"
+
+#include <QDebug>
+#include <QEventLoop>
+#include <QFile>
+#include <QJsonDocument>
+#include <QJsonObject>
+#include <QList>
+#include <QMap>
+#include <QTimer>
+#include <algorithm>
+#include <cmath>
+
+namespace bench {
+namespace {
+// Let the last words reach the server before Stop, as a user would.
+constexpr int kTailMs = 500;
+// On top of the file itself: connect, drain and the backend's own timeouts.
+constexpr int kSessionSlackMs = 30'000;
+
+constexpr const char *kPhases[] = {"connect_ms", "first_partial_ms", "stop_to_last_final_ms"};
+
+/// Nearest-rank percentile of non-empty, sorted `v`; `p` in 1..100.
+qint64 percentile(const QList<qint64> &v, int p) {
+    const auto rank = static_cast<qsizetype>(std::ceil(p / 100.0 * v.size()));
+    return v.at(std::max<qsizetype>(rank, 1) - 1);
+}
+} // namespace
+
+int run(AsrController &asr, const QString &wavPath, int iterations, QTextStream &out) {
+    QFile f(wavPath);
+    if (!f.open(QIODevice::ReadOnly)) {
+        qWarning().noquote() << "Bench: cannot open" << wavPath << "—" << f.errorString();
+        return 1;
+    }
+    const wav::Parsed parsed = wav::parse(f.readAll());
+    if (!parsed.ok) {
+        qWarning().noquote() << "Bench:" << wavPath << "—" << parsed.error;
+        return 1;
+    }
+    const qint64 fileMs = static_cast<qint64>(parsed.pcm.size()) / (2 * parsed.channels) *
+                          1000 / parsed.sampleRate;
+
+    QMap<QString, QList<qint64>> samples;  // phase → ms, sessions that reached it
+    int failed = 0;
+    for (int i = 0; i < iterations; ++i) {
+        QEventLoop loop;
+        QJsonObject stats;
+        bool errored = false;
+        bool timedOut = false;
+        QObject::connect(&asr, &AsrController::sessionStats, &loop, [&](const QString &json) {
+            stats = QJsonDocument::fromJson(json.toUtf8()).object();
+            loop.quit();
+        });
+        QObject::connect(&asr, &AsrController::errorOccurred, &loop,
+                         [&](const QString &) { errored = true; });
+        QTimer::singleShot(fileMs + kTailMs, &loop, [&asr]() { asr.stopRecording(); });
+        QTimer::singleShot(fileMs + kTailMs + kSessionSlackMs, &loop, [&]() {
+            timedOut = true;
+            loop.quit();
+        });
+        asr.startRecording();
+        // A session that fails on the spot has already reported.
+        if (stats.isEmpty()) loop.exec();
+        if (timedOut) {
+            qWarning() << "Bench: session" << i + 1 << "did not finish; cancelling";
+            asr.cancelRecording();
+        }
+        if (timedOut || errored) {
+            ++failed;
+            continue;
+        }
+        const QJsonObject latency = stats.value(QStringLiteral("latency")).toObject();
+        for (const char *phase : kPhases) {
+            const qint64 ms = latency.value(QLatin1String(phase)).toInteger(-1);
+            if (ms >= 0) samples[QLatin1String(phase)].append(ms);
+        }
+        qInfo() << "Bench: session" << i + 1 << "of" << iterations << "done";
+    }
+
+    QJsonObject summary;
+    summary.insert(QStringLiteral("iterations"), iterations);
+    summary.insert(QStringLiteral("failed"), failed);
+    summary.insert(QStringLiteral("file_ms"), fileMs);
+    for (const char *phase : kPhases) {
+        QList<qint64> v = samples.value(QLatin1String(phase));
+        std::sort(v.begin(), v.end());
+        QJsonObject o;
+        o.insert(QStringLiteral("n"), static_cast<qint64>(v.size()));
+        if (!v.isEmpty()) {
+            o.insert(QStringLiteral("p50"), percentile(v, 50));
+            o.insert(QStringLiteral("p90"), percentile(v, 90));
+            o.insert(QStringLiteral("max"), v.last());
+        }
+        summary.insert(QLatin1String(phase), o);
+    }
+    out << QJsonDocument(summary).toJson(QJsonDocument::Compact) << "\n";
+    return failed > 0 ? 1 : 0;
+}
+
+} // namespace bench
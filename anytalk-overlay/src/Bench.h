@@ -0,0 +1,18 @@
+#pragma once
+#include <QString>
+#include <QTextStream>
+
+class AsrController;
+
+namespace bench {
+
+/// `anytalk-overlay --simulate-audio <wav> --bench <n>`: run `iterations`
+/// sessions back to back on `asr` (configured, fed from that WAV), each
+/// stopped once the file has played, then print one JSON line to `out`
+/// with n / p50 / p90 / max of the SessionStats latency phases connect_ms,
+/// first_partial_ms and stop_to_last_final_ms. A phase a session never
+/// reached is left out of its percentiles. Returns a process exit code —
+/// 1 if any session failed. Needs a running event loop, not a bus name.
+int run(AsrController &asr, const QString &wavPath, int iterations, QTextStream &out);
+
+} // namespace bench
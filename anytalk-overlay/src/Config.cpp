@@ -1,14 +1,18 @@
 #include "Config.h"
 
+#include <QDebug>
 #include <QDir>
 #include <QFile>
 #include <QSaveFile>
 #include <QStringBuilder>
 #include <QTextStream>
+#include <algorithm>
 
 namespace {
 constexpr const char *kConfigSubpath = "/.config/fcitx5/conf/anytalk.conf";
 
+QString filePathOverride;  // --config
+
 QString joinKey(const QString &section, const QString &key) {
     if (section.isEmpty()) return key;
     return section % QLatin1Char('/') % key;
@@ -25,12 +29,93 @@ bool toBool(const QString &v, bool fallback) {
     return fallback;
 }
 
+// "Volcengine@english/Language" → {"Volcengine", "english", "Language"}.
+// False for keys without a profile suffix.
+bool splitProfileKey(const QString &full, QString *section, QString *profile,
+                     QString *key) {
+    const int slash = full.indexOf(QLatin1Char('/'));
+    if (slash <= 0) return false;
+    const QString head = full.left(slash);
+    const int at = head.indexOf(QLatin1Char('@'));
+    if (at <= 0 || at == head.size() - 1) return false;
+    *section = head.left(at);
+    *profile = head.mid(at + 1);
+    *key = full.mid(slash + 1);
+    return true;
+}
+
 } // namespace
 
+QStringList OverlayConfig::profiles() const {
+    QStringList names;
+    QString section, profile, key;
+    for (auto it = backendOptions.constBegin(); it != backendOptions.constEnd(); ++it) {
+        if (splitProfileKey(it.key(), &section, &profile, &key) && !names.contains(profile)) {
+            names.append(profile);
+        }
+    }
+    names.sort();
+    return names;
+}
+
+std::optional<OverlayConfig> OverlayConfig::withProfile(const QString &name) const {
+    OverlayConfig out = *this;
+    out.activeProfile.clear();
+    if (name.isEmpty()) return out;
+    if (!profiles().contains(name)) return std::nullopt;
+
+    QString section, profile, key;
+    for (auto it = backendOptions.constBegin(); it != backendOptions.constEnd(); ++it) {
+        if (!splitProfileKey(it.key(), &section, &profile, &key) || profile != name) continue;
+        const QString val = it.value().toString();
+        // Mirror load(): these two [Asr] keys live in typed fields.
+        if (section == QLatin1String("Asr") && key == QLatin1String("Backend")) {
+            if (!val.isEmpty()) out.backend = val;
+        } else if (section == QLatin1String("Asr") &&
+                   key == QLatin1String("RemoveTrailingPunctuation")) {
+            out.removeTrailingPunctuation = toBool(val, false);
+        } else {
+            out.backendOptions.insert(joinKey(section, key), val);
+        }
+    }
+    out.activeProfile = name;
+    return out;
+}
+
+OverlayConfig OverlayConfig::resolved() const {
+    QString name = qEnvironmentVariable("ANYTALK_PROFILE").trimmed();
+    if (name.isEmpty()) name = str(QStringLiteral("Asr"), QStringLiteral("Profile")).trimmed();
+    if (auto cfg = withProfile(name)) return *cfg;
+    qWarning() << "OverlayConfig: unknown profile" << name << "— known:" << profiles()
+               << "; using base config";
+    return *withProfile(QString());
+}
+
 QString OverlayConfig::configFilePath() {
+    if (!filePathOverride.isEmpty()) return filePathOverride;
+    const QString env = qEnvironmentVariable("ANYTALK_CONFIG");
+    if (!env.isEmpty()) return env;
     return QDir::homePath() + QString::fromLatin1(kConfigSubpath);
 }
 
+void OverlayConfig::setConfigFilePath(const QString &path) { filePathOverride = path; }
+
+bool OverlayConfig::isSecretKey(const QString &key) {
+    return key.endsWith(QLatin1String("Token"), Qt::CaseInsensitive) ||
+           key.endsWith(QLatin1String("Secret"), Qt::CaseInsensitive) ||
+           key.endsWith(QLatin1String("Password"), Qt::CaseInsensitive) ||
+           key.compare(QLatin1String("ApiKey"), Qt::CaseInsensitive) == 0;
+}
+
+bool OverlayConfig::isSecretSection(const QString &section) {
+    static const QString headers = QStringLiteral("VolcengineHeaders");
+    return section == headers || section.startsWith(headers + QLatin1Char('@'));
+}
+
+QString OverlayConfig::redacted(const QString &value) {
+    return QStringLiteral("****(len=%1)").arg(value.size());
+}
+
 QString OverlayConfig::str(const QString &section, const QString &key,
                             const QString &fallback) const {
     const auto v = backendOptions.value(joinKey(section, key));
@@ -42,6 +127,25 @@ bool OverlayConfig::boolean(const QString &section, const QString &key, bool fal
     return v.isValid() ? toBool(v.toString(), fallback) : fallback;
 }
 
+int OverlayConfig::intIn(const QString &section, const QString &key, int fallback, int lo,
+                         int hi) const {
+    const QString raw = str(section, key).trimmed();
+    if (raw.isEmpty()) return fallback;
+    bool ok = false;
+    const int v = raw.toInt(&ok);
+    const QString what = QStringLiteral("[%1] %2 = %3").arg(section, key, raw);
+    if (!ok) {
+        qWarning().noquote() << "OverlayConfig:" << what << "is not an integer; using" << fallback;
+        return fallback;
+    }
+    const int clamped = std::clamp(v, lo, hi);
+    if (clamped != v) {
+        qWarning().noquote() << "OverlayConfig:" << what << "is outside"
+                             << QStringLiteral("%1..%2;").arg(lo).arg(hi) << "using" << clamped;
+    }
+    return clamped;
+}
+
 bool OverlayConfig::isUsable() const {
     if (backend == QLatin1String("volcengine")) {
         return !str(QStringLiteral("Volcengine"), QStringLiteral("AppID")).isEmpty() &&
@@ -114,17 +218,9 @@ OverlayConfig OverlayConfig::load() {
     return cfg;
 }
 
-bool OverlayConfig::save() const {
-    const QString path = configFilePath();
-    QDir().mkpath(QFileInfo(path).absolutePath());
-
-    QSaveFile f(path);
-    if (!f.open(QIODevice::WriteOnly | QIODevice::Text)) return false;
-    QTextStream out(&f);
-    out.setEncoding(QStringConverter::Utf8);
-
-    out << "# anytalk-overlay configuration\n";
-    out << "# Edit via the SettingsDialog; the file format follows fcitx5 INI.\n\n";
+QString OverlayConfig::toIni(bool redactSecrets) const {
+    QString text;
+    QTextStream out(&text);
     out << "[Asr]\n";
     out << "Backend = " << backend << "\n";
     out << "RemoveTrailingPunctuation = " << (removeTrailingPunctuation ? "True" : "False") << "\n";
@@ -141,12 +237,31 @@ bool OverlayConfig::save() const {
     std::sort(sections.begin(), sections.end());
     for (const auto &section : sections) {
         out << "\n[" << section << "]\n";
+        const bool secretSection = redactSecrets && isSecretSection(section);
         const auto &kv = bySection[section];
         auto keys = kv.keys();
         std::sort(keys.begin(), keys.end());
         for (const auto &k : keys) {
-            out << k << " = " << kv.value(k).toString() << "\n";
+            const QString v = kv.value(k).toString();
+            const bool hide = secretSection || (redactSecrets && isSecretKey(k));
+            out << k << " = " << (hide ? redacted(v) : v) << "\n";
         }
     }
+    return text;
+}
+
+bool OverlayConfig::save() const {
+    const QString path = configFilePath();
+    QDir().mkpath(QFileInfo(path).absolutePath());
+
+    QSaveFile f(path);
+    if (!f.open(QIODevice::WriteOnly | QIODevice::Text)) return false;
+    QTextStream out(&f);
+    out.setEncoding(QStringConverter::Utf8);
+
+    out << "# anytalk-overlay configuration\n";
+    out << "# Edit via the SettingsDialog; the file format follows fcitx5 INI.\n\n";
+    out << toIni(/*redactSecrets=*/false);
+    out.flush();
     return f.commit();
 }
@@ -1,6 +1,8 @@
 #pragma once
 #include <QString>
+#include <QStringList>
 #include <QVariantHash>
+#include <optional>
 
 /// User configuration loaded from ~/.config/fcitx5/conf/anytalk.conf.
 ///
@@ -18,6 +20,13 @@
 ///   ApiKey = sk-...
 ///   Model  = gpt-4o-mini-transcribe
 ///
+/// Optional keys are documented where they are read; `--print-config`
+/// shows the effective values.
+///
+/// Profiles: `[Section@name]` overrides keys of `[Section]` while profile
+/// `name` is active (ANYTALK_PROFILE, else `[Asr] Profile`). `[Overlay]`
+/// is read once at startup.
+///
 /// Legacy flat schema (still read for backwards compatibility):
 ///   AppID                = ...
 ///   AccessToken          = ...
@@ -29,20 +38,55 @@ struct OverlayConfig {
     bool removeTrailingPunctuation = false;
 
     // Per-backend bag — each backend pulls the keys it needs.
-    // Stored flat as "Section/Key" → string.
+    // Stored flat as "Section/Key" → string. Profile sections are kept
+    // verbatim ("Volcengine@english/Language") so save() round-trips them.
     QVariantHash backendOptions;
 
+    // Profile whose overrides are folded into this config; empty = base.
+    QString activeProfile;
+
     /// Helpers for typed access.
     QString str(const QString &section, const QString &key,
                 const QString &fallback = {}) const;
     bool boolean(const QString &section, const QString &key, bool fallback = false) const;
+    /// Integer in [lo, hi]; `fallback` when unset. A non-integer falls back
+    /// and an out-of-range value is clamped, both with a warning.
+    int intIn(const QString &section, const QString &key, int fallback, int lo, int hi) const;
 
     /// True when the active backend has the bare-minimum credentials it
     /// needs to start a session. Used to decide whether to launch the
     /// SettingsDialog instead of recording.
     bool isUsable() const;
 
+    /// Profile names defined in the file, sorted.
+    QStringList profiles() const;
+    /// Copy with `[Section@name]` keys laid over `[Section]`. An empty
+    /// name returns the base config; an unknown one returns std::nullopt.
+    std::optional<OverlayConfig> withProfile(const QString &name) const;
+    /// The effective config: ANYTALK_PROFILE, else `[Asr] Profile`,
+    /// applied via withProfile(). An unknown profile logs a warning and
+    /// falls back to the base config.
+    OverlayConfig resolved() const;
+
+    /// `[Asr]` first, then every other section sorted — the body of the
+    /// config file. With `redactSecrets`, values of isSecretKey() keys and
+    /// of isSecretSection() sections are replaced by redacted(); for
+    /// printing, never for saving.
+    QString toIni(bool redactSecrets) const;
+
+    /// Credential-bearing keys (AccessToken, ApiKey, *Secret, ...).
+    static bool isSecretKey(const QString &key);
+    /// Sections whose every value is treated as a credential:
+    /// `[VolcengineHeaders]` and its `@profile` variants, where gateway
+    /// auth can hide under any header name.
+    static bool isSecretSection(const QString &section);
+    /// "****(len=N)": enough to tell set from unset, nothing more.
+    static QString redacted(const QString &value);
+
+    /// `--config`, else ANYTALK_CONFIG, else
+    /// ~/.config/fcitx5/conf/anytalk.conf.
     static QString configFilePath();
+    static void setConfigFilePath(const QString &path);
     static OverlayConfig load();
     bool save() const;
 };
@@ -0,0 +1,257 @@
+#include "Logging.h"
+#include "Config.h"
+
+#include <QDateTime>
+#include <QDir>
+#include <QFile>
+#include <QFileInfo>
+#include <QJsonDocument>
+#include <QJsonObject>
+#include <QLoggingCategory>
+#include <QMutex>
+#include <QRegularExpression>
+#include <QString>
+#include <QtGlobal>
+#include <atomic>
+#include <cerrno>
+#include <cstdio>
+#include <cstring>
+#include <utility>
+
+namespace logging {
+
+namespace {
+constexpr qint64 kRotateBytes = 5LL * 1024 * 1024;
+constexpr int kKeepFiles = 3;
+
+QMutex fileMutex;
+QFile *logFile = nullptr;  // file sink only; lives for the process
+Format lineFormat = Format::Text;
+
+// Each list is complete before it is published and never changes after,
+// so handlers on any thread read it without a lock. addSecret() publishes
+// a grown copy and leaks the old one (a handler may still be scanning
+// it) — one per credential a config load names.
+std::atomic<const QStringList *> secrets{nullptr};
+// What redactingHandler forwards to; set once by install() before the
+// wrapper goes live.
+QtMessageHandler sinkHandler = nullptr;
+
+QString scrub(const QString &msg) {
+    const QStringList *list = secrets.load(std::memory_order_acquire);
+    if (!list) return msg;
+    QString out = msg;
+    for (const auto &s : *list) {
+        if (out.contains(s)) out.replace(s, OverlayConfig::redacted(s));
+    }
+    return out;
+}
+
+// Installed last, in front of whichever sink install() picked.
+void redactingHandler(QtMsgType type, const QMessageLogContext &ctx, const QString &msg) {
+    const QString clean = scrub(msg);
+    if (sinkHandler) {
+        sinkHandler(type, ctx, clean);
+    } else {
+        std::fprintf(stderr, "%s\n", qFormatLogMessage(type, ctx, clean).toLocal8Bit().constData());
+    }
+}
+
+const char *levelName(QtMsgType type) {
+    switch (type) {
+    case QtDebugMsg: return "debug";
+    case QtInfoMsg: return "info";
+    case QtWarningMsg: return "warning";
+    case QtCriticalMsg: return "critical";
+    case QtFatalMsg: return "fatal";
+    }
+    return "info";
+}
+
+QByteArray formatLine(QtMsgType type, const QMessageLogContext &ctx, const QString &msg) {
+    if (lineFormat == Format::Text) return qFormatLogMessage(type, ctx, msg).toUtf8();
+    QJsonObject o;
+    o.insert(QStringLiteral("ts"),
+             QDateTime::currentDateTime().toString(Qt::ISODateWithMs));
+    o.insert(QStringLiteral("level"), QLatin1String(levelName(type)));
+    // "AsrController: no audio for ..." → component + message.
+    static const QRegularExpression prefix(QStringLiteral("^([A-Za-z_][\\w:-]*): (.*)$"),
+                                           QRegularExpression::DotMatchesEverythingOption);
+    const auto m = prefix.match(msg);
+    if (m.hasMatch()) {
+        o.insert(QStringLiteral("component"), m.captured(1));
+        o.insert(QStringLiteral("msg"), m.captured(2));
+    } else {
+        o.insert(QStringLiteral("msg"), msg);
+    }
+    if (ctx.category && qstrcmp(ctx.category, "default") != 0) {
+        o.insert(QStringLiteral("category"), QLatin1String(ctx.category));
+    }
+    return QJsonDocument(o).toJson(QJsonDocument::Compact);
+}
+
+void stderrHandler(QtMsgType type, const QMessageLogContext &ctx, const QString &msg) {
+    const QByteArray line = formatLine(type, ctx, msg);
+    std::fprintf(stderr, "%s\n", line.constData());
+    std::fflush(stderr);
+}
+
+// sd-daemon(3) priority prefixes.
+const char *journalPrefix(QtMsgType type) {
+    switch (type) {
+    case QtDebugMsg: return "<7>";
+    case QtInfoMsg: return "<6>";
+    case QtWarningMsg: return "<4>";
+    case QtCriticalMsg: return "<3>";
+    case QtFatalMsg: return "<2>";
+    }
+    return "<6>";
+}
+
+void journalHandler(QtMsgType type, const QMessageLogContext &ctx, const QString &msg) {
+    const QByteArray line = formatLine(type, ctx, msg);
+    std::fprintf(stderr, "%s%s\n", journalPrefix(type), line.constData());
+    std::fflush(stderr);
+}
+
+bool openLogFile(const QString &path) {
+    logFile->setFileName(path);
+    return openPrivate(*logFile, QIODevice::WriteOnly | QIODevice::Append | QIODevice::Text);
+}
+
+// Runs inside fileHandler with fileMutex held: a qWarning from here would
+// re-enter the handler and deadlock. Plain libc calls only, failures go
+// straight to stderr.
+void rotate() {
+    const QString path = logFile->fileName();
+    const QByteArray base = QFile::encodeName(path);
+    auto numbered = [&base](int i) { return base + '.' + QByteArray::number(i); };
+    logFile->close();
+    std::remove(numbered(kKeepFiles - 1).constData());
+    for (int i = kKeepFiles - 2; i >= 1; --i) {
+        std::rename(numbered(i).constData(), numbered(i + 1).constData());
+    }
+    if (std::rename(base.constData(), numbered(1).constData()) != 0) {
+        std::fprintf(stderr, "anytalk-overlay: cannot rotate log file %s: %s\n",
+                     base.constData(), std::strerror(errno));
+    }
+    if (!openLogFile(path)) {
+        std::fprintf(stderr, "anytalk-overlay: cannot reopen log file %s; logging stops\n",
+                     base.constData());
+    }
+}
+
+void fileHandler(QtMsgType type, const QMessageLogContext &ctx, const QString &msg) {
+    const QByteArray line = formatLine(type, ctx, msg) + '\n';
+    QMutexLocker lock(&fileMutex);
+    if (logFile->isOpen()) {
+        logFile->write(line);
+        logFile->flush();
+        if (logFile->size() >= kRotateBytes) rotate();
+    }
+    if (type == QtFatalMsg) std::fputs(line.constData(), stderr);
+}
+
+void installSink(const QString &rawTarget, Format format) {
+    lineFormat = format;
+    const QString target = rawTarget.trimmed();
+    if (target.isEmpty() || target == QLatin1String("stderr")) {
+        // Text keeps Qt's own formatting; install() wraps it for redaction.
+        if (format == Format::Json) qInstallMessageHandler(stderrHandler);
+        return;
+    }
+
+    if (target == QLatin1String("journal")) {
+        qInstallMessageHandler(journalHandler);
+        std::fputs("anytalk-overlay: logging to stderr with journald priorities\n", stderr);
+        return;
+    }
+
+    const QString path = QFileInfo(target).absoluteFilePath();
+    QDir().mkpath(QFileInfo(path).absolutePath());
+    logFile = new QFile;
+    if (!openLogFile(path)) {
+        std::fprintf(stderr, "anytalk-overlay: cannot open log file %s; logging to stderr\n",
+                     qPrintable(path));
+        delete logFile;
+        logFile = nullptr;
+        return;
+    }
+    qInstallMessageHandler(fileHandler);
+    std::fprintf(stderr, "anytalk-overlay: logging to %s\n", qPrintable(path));
+}
+} // namespace
+
+bool formatFromString(const QString &s, Format *out) {
+    const QString v = s.trimmed().toLower();
+    if (v == QLatin1String("text")) *out = Format::Text;
+    else if (v == QLatin1String("json")) *out = Format::Json;
+    else return false;
+    return true;
+}
+
+void addSecret(const QString &value) {
+    if (value.size() < 4) return;
+    const QStringList *current = secrets.load(std::memory_order_acquire);
+    if (current && current->contains(value)) return;
+    auto *next = new QStringList(current ? *current : QStringList());
+    next->append(value);
+    secrets.store(next, std::memory_order_release);
+}
+
+void install(const QString &rawTarget, Format format) {
+    static bool installed = false;
+    if (std::exchange(installed, true)) return;
+    installSink(rawTarget, format);
+    // Take the sink out and put the wrapper in its place, so sinkHandler is
+    // final before anything can call redactingHandler.
+    sinkHandler = qInstallMessageHandler(nullptr);
+    qInstallMessageHandler(redactingHandler);
+}
+
+bool openPrivate(QFile &file, QIODevice::OpenMode mode) {
+    if (!file.open(mode)) return false;
+    file.setPermissions(QFileDevice::ReadOwner | QFileDevice::WriteOwner);
+    return true;
+}
+
+bool RateLimit::allow(int *suppressed) {
+    QMutexLocker lock(&mutex_);
+    if (sinceLast_.isValid() && sinceLast_.elapsed() < intervalMs_) {
+        ++suppressed_;
+        return false;
+    }
+    sinceLast_.start();
+    if (suppressed) *suppressed = suppressed_;
+    suppressed_ = 0;
+    return true;
+}
+
+void RateLimit::reset() {
+    QMutexLocker lock(&mutex_);
+    sinceLast_.invalidate();
+    suppressed_ = 0;
+}
+
+QDebug operator<<(QDebug d, Suppressed s) {
+    if (s.count <= 0) return d;
+    QDebugStateSaver saver(d);
+    d.nospace() << "(" << s.count << " similar suppressed)";
+    return d;
+}
+
+bool setLevel(const QString &level) {
+    const QString v = level.trimmed().toLower();
+    if (v == QLatin1String("debug")) {
+        QLoggingCategory::setFilterRules(QStringLiteral("*.debug=true"));
+    } else if (v == QLatin1String("info")) {
+        QLoggingCategory::setFilterRules(QStringLiteral("*.debug=false"));
+    } else if (v == QLatin1String("warning")) {
+        QLoggingCategory::setFilterRules(QStringLiteral("*.debug=false\n*.info=false"));
+    } else {
+        return false;
+    }
+    return true;
+}
+
+} // namespace logging
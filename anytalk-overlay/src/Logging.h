@@ -0,0 +1,81 @@
+#pragma once
+#include <QDebug>
+#include <QElapsedTimer>
+#include <QFile>
+#include <QMutex>
+#include <QString>
+
+/// Where qDebug/qInfo/qWarning output goes, chosen by `--log-file`, else
+/// ANYTALK_LOG_FILE:
+///   empty / "stderr"  Qt's default handler. Under D-Bus activation stderr
+///                     is the bus daemon's, which systemd user sessions
+///                     route to the journal.
+///   "journal"         stderr with sd-daemon `<N>` priority prefixes, so
+///                     journald keeps warning vs info apart.
+///   anything else     a file path, owner-only (0600), rotated at 5 MB
+///                     keeping 3 files (`path`, `path.1`, `path.2`).
+/// The chosen sink is announced once on stderr.
+///
+/// Orthogonal to the sink, `--log-format` / ANYTALK_LOG_FORMAT picks the
+/// line format: "text" (Qt's default pattern) or "json", one object per
+/// line for log shippers:
+///   {"ts":"2026-10-16T09:12:03.481+08:00","level":"warning",
+///    "component":"VolcengineBackend","msg":"ws error ..."}
+/// `component` is the "ClassName: " prefix our messages start with; it is
+/// omitted when a message has none.
+namespace logging {
+
+enum class Format { Text, Json };
+
+/// "text" | "json" (case-insensitive); false for anything else.
+bool formatFromString(const QString &s, Format *out);
+
+/// Once, early in main(); later calls are ignored.
+void install(const QString &target, Format format = Format::Text);
+
+/// Replace `value` with OverlayConfig::redacted() in every message from
+/// now on, whatever logs it — our code or Qt's own network categories at
+/// --log-level debug. For credentials, while a config is loaded (main
+/// thread); values under 4 chars are ignored (too likely to match
+/// ordinary text).
+void addSecret(const QString &value);
+
+/// Open `file` with `mode` and make it owner-only (0600). For everything
+/// we write that holds voice, transcripts or tokens: the log file, the
+/// transcript history, debug WAVs and frame logs.
+bool openPrivate(QFile &file, QIODevice::OpenMode mode);
+
+/// "debug" | "info" | "warning" — the lowest level printed (default
+/// info). False for anything else.
+bool setLevel(const QString &level);
+
+/// Lets one call site log at most once per interval, counting the calls it
+/// swallowed in between. Keep one per call site (a member, or a function
+/// static); safe across threads.
+///   int skipped = 0;
+///   if (limit_.allow(&skipped))
+///       qWarning() << "X: read failed" << logging::Suppressed{skipped};
+class RateLimit {
+public:
+    explicit RateLimit(qint64 intervalMs) : intervalMs_(intervalMs) {}
+
+    /// True when the caller should log now. `suppressed` receives how many
+    /// calls were refused since the last allowed one.
+    bool allow(int *suppressed = nullptr);
+    /// Next call is allowed immediately; e.g. at session start.
+    void reset();
+
+private:
+    QMutex mutex_;
+    QElapsedTimer sinceLast_;
+    qint64 intervalMs_;
+    int suppressed_ = 0;
+};
+
+/// Streams "(N similar suppressed)", or nothing when N is 0.
+struct Suppressed {
+    int count = 0;
+};
+QDebug operator<<(QDebug d, Suppressed s);
+
+} // namespace logging
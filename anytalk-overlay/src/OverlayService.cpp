@@ -1,8 +1,10 @@
 #include "OverlayService.h"
 #include "AsrController.h"
+#include "Config.h"
 #include "OverlayWindow.h"
 
 #include <QDBusConnection>
+#include <QDBusConnectionInterface>
 #include <QDBusError>
 #include <QDebug>
 
@@ -14,7 +16,7 @@ constexpr const char *kPath = "/overlay";
 OverlayService::OverlayService(OverlayWindow *window, AsrController *asr, QObject *parent)
     : QObject(parent), window_(window), asr_(asr) {}
 
-bool OverlayService::registerOnBus() {
+bool OverlayService::registerOnBus(bool replace) {
     auto bus = QDBusConnection::sessionBus();
     if (!bus.isConnected()) {
         qWarning() << "OverlayService: cannot connect to session bus";
@@ -26,14 +28,35 @@ bool OverlayService::registerOnBus() {
         qWarning() << "OverlayService: registerObject failed";
         return false;
     }
-    if (!bus.registerService(kService)) {
+    auto *iface = bus.interface();
+    if (replace) {
+        const QDBusReply<uint> pid = iface->servicePid(QString::fromLatin1(kService));
+        if (pid.isValid()) {
+            qInfo() << "OverlayService: replacing running overlay, pid" << pid.value();
+        }
+    }
+    const QDBusReply<QDBusConnectionInterface::RegisterServiceReply> reply =
+        iface->registerService(QString::fromLatin1(kService),
+                               replace ? QDBusConnectionInterface::ReplaceExistingService
+                                       : QDBusConnectionInterface::DontQueueService,
+                               QDBusConnectionInterface::AllowReplacement);
+    if (!reply.isValid() || reply.value() != QDBusConnectionInterface::ServiceRegistered) {
         qWarning() << "OverlayService: registerService failed —"
-                   << bus.lastError().message();
+                   << (reply.isValid() ? QStringLiteral("name taken") : reply.error().message());
         return false;
     }
+    bus.connect(QStringLiteral("org.freedesktop.DBus"), QStringLiteral("/org/freedesktop/DBus"),
+                QStringLiteral("org.freedesktop.DBus"), QStringLiteral("NameLost"), this,
+                SLOT(onNameLost(QString)));
     return true;
 }
 
+void OverlayService::onNameLost(const QString &name) {
+    if (name != QLatin1String(kService)) return;
+    qInfo() << "OverlayService: bus name taken over by a new instance";
+    emit nameLost();
+}
+
 void OverlayService::ToggleRecording() {
     if (asr_) asr_->toggleRecording();
 }
@@ -42,6 +65,14 @@ void OverlayService::StopRecording() {
     if (asr_) asr_->stopRecording();
 }
 
+void OverlayService::FlushRecording() {
+    if (asr_) asr_->flushRecording();
+}
+
+bool OverlayService::CommitPartial() {
+    return asr_ && asr_->commitPartial();
+}
+
 void OverlayService::CancelRecording() {
     if (asr_) asr_->cancelRecording();
     // Also serves as the escape-while-waiting-for-Ack path so the user
@@ -49,6 +80,72 @@ void OverlayService::CancelRecording() {
     emit cancelEscape();
 }
 
+void OverlayService::SetInputGain(double db) {
+    if (asr_) asr_->setInputGainDb(db);
+}
+
+double OverlayService::InputGain() const { return asr_ ? asr_->inputGainDb() : 0.0; }
+
+bool OverlayService::SetProfile(const QString &name) {
+    if (!asr_) return false;
+    const QString profile = name.trimmed();
+    const auto cfg = OverlayConfig::load().withProfile(profile);
+    if (!cfg) {
+        qWarning() << "OverlayService: SetProfile: unknown profile" << name;
+        return false;
+    }
+    // [Overlay] is read once by main(); a profile can't change it later.
+    const QString overlayPrefix = QStringLiteral("Overlay@") + profile + QLatin1Char('/');
+    QStringList ignored;
+    for (auto it = cfg->backendOptions.constBegin(); it != cfg->backendOptions.constEnd(); ++it) {
+        if (it.key().startsWith(overlayPrefix)) ignored.append(it.key().mid(overlayPrefix.size()));
+    }
+    if (!ignored.isEmpty()) {
+        ignored.sort();
+        qWarning() << "OverlayService: SetProfile:" << profile
+                   << "overrides [Overlay] keys, which are fixed at startup; ignoring" << ignored;
+    }
+    // applyConfig refuses while a session is active.
+    return asr_->applyConfig(*cfg);
+}
+
+QString OverlayService::ActiveProfile() const {
+    return asr_ ? asr_->activeProfile() : QString();
+}
+
+QString OverlayService::Languages() const {
+    return asr_ ? asr_->languagesJson() : QStringLiteral("{}");
+}
+
+QStringList OverlayService::SetLanguages(const QStringList &langs) {
+    return asr_ ? asr_->setLanguages(langs) : QStringList();
+}
+
+bool OverlayService::SetAudioSource(const QString &source) {
+    return asr_ && asr_->setAudioSource(source);
+}
+
+void OverlayService::InjectTranscript(const QString &text, bool partial) {
+    if (!testMode_) {
+        sendErrorReply(QDBusError::AccessDenied,
+                       QStringLiteral("InjectTranscript requires --test-mode"));
+        return;
+    }
+    if (asr_) asr_->injectTranscript(text, partial);
+}
+
+QString OverlayService::AudioInfo() const {
+    return asr_ ? asr_->audioInfoJson() : QStringLiteral("{}");
+}
+
+QString OverlayService::EffectiveConfig() const {
+    return asr_ ? asr_->effectiveConfigJson() : QStringLiteral("{}");
+}
+
+QString OverlayService::LastError() const {
+    return asr_ ? asr_->lastErrorJson() : QStringLiteral("{}");
+}
+
 void OverlayService::OpenSettings() { emit openSettingsRequested(); }
 
 void OverlayService::Acknowledge() { emit ackReceived(); }
@@ -1,4 +1,5 @@
 #pragma once
+#include <QDBusContext>
 #include <QObject>
 
 class OverlayWindow;
@@ -19,35 +20,112 @@ class AsrController;
 /// Methods:
 ///   ToggleRecording()      idempotent: start if idle, stop if active
 ///   StopRecording()        explicit stop (drain server finals → CommitText)
+///   FlushRecording()       stop now: commit finals + current partial
+///                          without waiting for the server drain
+///   CommitPartial() → b    commit finals + current partial now and keep
+///                          recording (CommitText mid-session; Acknowledge
+///                          does not end the session). The server's later
+///                          final for that utterance is trimmed so nothing
+///                          is committed twice. False if not recording or
+///                          nothing to commit
 ///   CancelRecording()      drop in-flight session, no commit; also serves
 ///                          as the user/addon "exit immediately" escape
 ///                          while the overlay is waiting for the post-
 ///                          commit Acknowledge
 ///   Acknowledge()          addon-→-overlay: commitString done, please exit
 ///   OpenSettings()         bring up the SettingsDialog (synchronous)
+///   SetInputGain(d)        software mic gain in dB (-20..30), applied
+///                          immediately; not persisted to the config
+///   InputGain() → d        current software gain in dB
+///   SetProfile(s) → b      switch to config profile `s` ("" = base) for
+///                          the next session; false if the profile is
+///                          unknown or a session is active. `[Overlay]`
+///                          keys are fixed at startup and stay as they are
+///   ActiveProfile() → s    profile in effect ("" = base)
+///   Languages() → s        JSON {"supported":[..],"selected":[..]}
+///   SetLanguages(as) → as  recognition languages for the next session,
+///                          e.g. ["zh-CN","en-US"]; returns the accepted
+///                          subset (unknown codes dropped, empty = unchanged)
+///   SetAudioSource(s) → b  "mic" | "monitor" (system audio) for the next
+///                          session; false if unknown or a session is active
+///   InjectTranscript(s,b)  --test-mode only: deliver `s` as a final (or a
+///                          partial when b is true) as if the ASR backend
+///                          sent it; no audio or network. Outside a session
+///                          a final is committed at once. Rejected with
+///                          org.freedesktop.DBus.Error.AccessDenied when
+///                          test mode is off
+///   AudioInfo() → s        JSON: capture device, rate, channels, format,
+///                          fragment size, downmix, filter/gain stages
+///   EffectiveConfig() → s  JSON: config file, profile, the applied config
+///                          as INI with tokens redacted, ANYTALK_* env
+///                          overrides in effect, runtime gain / languages /
+///                          audio source; never the raw token
+///   LastError() → s        JSON {"ts","message","session","recent"} for
+///                          the newest of the last 10 errors, "{}" if none;
+///                          cleared once a later session drains cleanly
 ///
 /// Signals:
-///   StateChanged(s)        idle / connecting / recording / error
+///   StateChanged(s)        idle / connecting / recording / processing /
+///                          error; processing = audio done, a non-streaming
+///                          backend (Volcengine nostream) is still recognizing
 ///   TranscriptPartial(s)   streaming preedit text
 ///   TranscriptFinal(s)     committed segment (server-side final)
+///   TranscriptFinalMeta(s,x,x,i)
+///                          only with [Asr] FinalMetadata = true: the same
+///                          segment plus its audio start/end ms (-1 if the
+///                          server gave none) and character count
 ///   AudioLevel(d)          0..1, ~20 Hz
 ///   ErrorOccurred(s)       human-readable error
+///   Warning(s)             non-fatal condition keyword: "clipping" (mic
+///                          gain too hot, rate-limited) or "audio_stalled"
+///                          (no audio for [Audio] StallTimeout s, default
+///                          5; the session then fails) or "shutting_down"
+///                          (SIGTERM/SIGINT received; a live session is
+///                          drained first, see [Overlay] ShutdownGrace)
+///                          or "asr_fallback" (primary backend failed to
+///                          connect; [Asr] FallbackProfile serves the
+///                          session)
+///   SessionStats(s)        once per session, as it ends: JSON with
+///                          duration, chunks captured / sent, audio seconds
+///                          sent, dropped chunks, peak level, final count
+///                          and a "latency" object (connect, first audio,
+///                          recording, first partial / final, stop → last
+///                          final; ms, -1 if the phase didn't happen)
 ///   CommitText(s)          final text ready to commit; addon must call
 ///                          Acknowledge() after handling so overlay can exit
 ///   Cancelled()            cancel/Esc completed; overlay will exit
-class OverlayService : public QObject {
+class OverlayService : public QObject, protected QDBusContext {
     Q_OBJECT
     Q_CLASSINFO("D-Bus Interface", "org.fcitx.Fcitx5.AnyTalk.Overlay")
 public:
     OverlayService(OverlayWindow *window, AsrController *asr, QObject *parent = nullptr);
 
-    bool registerOnBus();
+    /// Claim the bus name. The name is always registered as replaceable;
+    /// with `replace` we also take it from a running overlay, which then
+    /// sees NameLost and exits (see nameLost).
+    bool registerOnBus(bool replace = false);
+
+    /// Enables InjectTranscript. Off unless main() saw `--test-mode`.
+    void setTestMode(bool on) { testMode_ = on; }
 
 public slots:
     Q_SCRIPTABLE void ToggleRecording();
     Q_SCRIPTABLE void StopRecording();
+    Q_SCRIPTABLE void FlushRecording();
+    Q_SCRIPTABLE bool CommitPartial();
     Q_SCRIPTABLE void CancelRecording();
     Q_SCRIPTABLE void OpenSettings();
+    Q_SCRIPTABLE void SetInputGain(double db);
+    Q_SCRIPTABLE double InputGain() const;
+    Q_SCRIPTABLE bool SetProfile(const QString &name);
+    Q_SCRIPTABLE QString ActiveProfile() const;
+    Q_SCRIPTABLE QString Languages() const;
+    Q_SCRIPTABLE QStringList SetLanguages(const QStringList &langs);
+    Q_SCRIPTABLE bool SetAudioSource(const QString &source);
+    Q_SCRIPTABLE QString AudioInfo() const;
+    Q_SCRIPTABLE QString EffectiveConfig() const;
+    Q_SCRIPTABLE QString LastError() const;
+    Q_SCRIPTABLE void InjectTranscript(const QString &text, bool partial);
     /// Addon → overlay: ic->commitString() finished, overlay can exit.
     Q_SCRIPTABLE void Acknowledge();
 
@@ -55,8 +133,12 @@ signals:
     Q_SCRIPTABLE void StateChanged(const QString &state);
     Q_SCRIPTABLE void TranscriptPartial(const QString &text);
     Q_SCRIPTABLE void TranscriptFinal(const QString &text);
+    Q_SCRIPTABLE void TranscriptFinalMeta(const QString &text, qint64 startMs, qint64 endMs,
+                                          int chars);
     Q_SCRIPTABLE void AudioLevel(double level);
     Q_SCRIPTABLE void ErrorOccurred(const QString &text);
+    Q_SCRIPTABLE void Warning(const QString &kind);
+    Q_SCRIPTABLE void SessionStats(const QString &json);
     /// Final text ready to commit; addon calls Acknowledge() afterwards.
     Q_SCRIPTABLE void CommitText(const QString &text);
     /// Cancel completed (Esc or addon-initiated CancelRecording).
@@ -69,8 +151,14 @@ signals:
     void ackReceived();
     /// In-process: cancel arrived while overlay was awaiting Acknowledge.
     void cancelEscape();
+    /// In-process: another overlay took our bus name (`--replace`).
+    void nameLost();
+
+private slots:
+    void onNameLost(const QString &name);
 
 private:
     OverlayWindow *window_;
     AsrController *asr_;
+    bool testMode_ = false;
 };
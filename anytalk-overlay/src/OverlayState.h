@@ -7,18 +7,23 @@
 /// Two representations live here:
 ///   - `state::State` — type-safe enum used internally by AsrController.
 ///     Use this for `if (currentState_ == state::State::Idle)` style logic.
-///   - `state::Idle / Connecting / Recording / Error` — QString constants
-///     used on signals and D-Bus to keep wire compatibility with existing
-///     subscribers (waybar custom modules, the fcitx5 addon).
+///   - `state::Idle / Connecting / Recording / Processing / Error` —
+///     QString constants used on signals and D-Bus to keep wire
+///     compatibility with existing subscribers (waybar custom modules,
+///     the fcitx5 addon).
 ///
-/// Convert enum → string at signal-emission time via `state::toString()`.
+/// Convert enum → string at signal-emission time via `state::toString()`,
+/// and back with `state::fromString()`.
 namespace state {
 
-enum class State { Idle, Connecting, Recording, Error };
+/// Processing: audio is done and a backend that doesn't stream results
+/// (Volcengine nostream) is working on the final; idle once it arrives.
+enum class State { Idle, Connecting, Recording, Processing, Error };
 
 inline const QString Idle       = QStringLiteral("idle");
 inline const QString Connecting = QStringLiteral("connecting");
 inline const QString Recording  = QStringLiteral("recording");
+inline const QString Processing = QStringLiteral("processing");
 inline const QString Error      = QStringLiteral("error");
 
 inline const QString &toString(State s) {
@@ -26,9 +31,19 @@ inline const QString &toString(State s) {
     case State::Idle:       return Idle;
     case State::Connecting: return Connecting;
     case State::Recording:  return Recording;
+    case State::Processing: return Processing;
     case State::Error:      return Error;
     }
     return Idle; // unreachable; silences -Wreturn-type
 }
 
+/// Unknown strings map to Idle.
+inline State fromString(const QString &s) {
+    if (s == Connecting) return State::Connecting;
+    if (s == Recording)  return State::Recording;
+    if (s == Processing) return State::Processing;
+    if (s == Error)      return State::Error;
+    return State::Idle;
+}
+
 } // namespace state
@@ -105,6 +105,13 @@ void OverlayWindow::onStateChanged(const QString &newState) {
         enterListening(/*connecting=*/false);
     } else if (newState == state::Connecting) {
         enterListening(/*connecting=*/true);
+    } else if (newState == state::Processing) {
+        if (vis_ != Vis::Active) return;
+        statusDot_->setMode(StatusDot::Mode::Connecting);
+        bars_->setLevel(0.0);
+        if (finalText_.isEmpty() && partialText_.isEmpty()) {
+            setTranscript(QStringLiteral("识别中…"), /*dim=*/true);
+        }
     } else if (newState == state::Error) {
         if (vis_ != Vis::Error) vis_ = Vis::Error;
     } else {
@@ -0,0 +1,166 @@
+#include "PostProcess.h"
+#include "Config.h"
+
+#include <QDebug>
+#include <QRegularExpression>
+#include <algorithm>
+
+namespace postprocess {
+
+namespace {
+const QString kStripTrailingPunct = QStringLiteral("strip_trailing_punct");
+
+QString stripTrailingPunct(const QString &text) {
+    static const QString puncts = QStringLiteral("，。！？、；：,.!?;:");
+    QString out = text;
+    while (!out.isEmpty() && puncts.contains(out.back())) out.chop(1);
+    return out;
+}
+
+QString capitalizeFirst(const QString &text) {
+    QString out = text;
+    for (QChar &c : out) {
+        if (!c.isLetter()) continue;
+        c = c.toUpper();
+        break;
+    }
+    return out;
+}
+
+QString spokenNewline(const QString &text) {
+    // Eat the punctuation ITN tends to hang on the spoken command too, so
+    // "第一行，换行。" becomes "第一行\n" rather than "第一行，\n。".
+    static const QRegularExpression re(
+        QStringLiteral("[，,]?\\s*(?:换行|new ?line)[，,。.!！]?"),
+        QRegularExpression::CaseInsensitiveOption);
+    QString out = text;
+    return out.replace(re, QStringLiteral("\n"));
+}
+
+QString collapseSpaces(const QString &text) {
+    static const QRegularExpression re(QStringLiteral("[ \\t]{2,}"));
+    QString out = text;
+    return out.replace(re, QStringLiteral(" "));
+}
+} // namespace
+
+Transform byName(const QString &name) {
+    if (name == kStripTrailingPunct) return stripTrailingPunct;
+    if (name == QLatin1String("capitalize_first")) return capitalizeFirst;
+    if (name == QLatin1String("spoken_newline")) return spokenNewline;
+    if (name == QLatin1String("collapse_spaces")) return collapseSpaces;
+    return {};
+}
+
+Pipeline Pipeline::fromConfig(const OverlayConfig &cfg) {
+    Pipeline p;
+    QStringList names;
+    const auto raw = cfg.str(QStringLiteral("Asr"), QStringLiteral("PostProcess"));
+    for (const auto &part : raw.split(QLatin1Char(','), Qt::SkipEmptyParts)) {
+        names.append(part.trimmed().toLower());
+    }
+    if (cfg.removeTrailingPunctuation && !names.contains(kStripTrailingPunct)) {
+        names.append(kStripTrailingPunct);
+    }
+    for (const auto &name : names) {
+        if (auto t = byName(name)) {
+            p.append(std::move(t));
+        } else {
+            qWarning() << "postprocess: unknown transform" << name << "— skipped";
+        }
+    }
+    return p;
+}
+
+QString Pipeline::apply(const QString &text) const {
+    QString out = text;
+    for (const auto &step : steps_) out = step(out);
+    return out;
+}
+
+CommandMap CommandMap::fromConfig(const OverlayConfig &cfg) {
+    static const QString prefix = QStringLiteral("SpokenCommands/");
+    CommandMap map;
+    for (auto it = cfg.backendOptions.constBegin(); it != cfg.backendOptions.constEnd(); ++it) {
+        if (!it.key().startsWith(prefix)) continue;
+        const QString phrase = it.key().mid(prefix.size());
+        const QString action = it.value().toString();
+        if (action == QLatin1String("newline")) {
+            map.add(phrase, Action::Newline);
+        } else if (action == QLatin1String("commit")) {
+            map.add(phrase, Action::Commit);
+        } else if (action.startsWith(QLatin1String("insert:")) && action.size() > 7) {
+            map.add(phrase, Action::Insert, action.mid(7));
+        } else {
+            qWarning() << "postprocess: unknown spoken command action" << action << "for"
+                       << phrase << "— skipped";
+        }
+    }
+    return map;
+}
+
+void CommandMap::add(const QString &phrase, Action action, const QString &text) {
+    if (phrase.isEmpty()) return;
+    Command cmd{phrase, action, text};
+    // Keep longest-first so apply() can take the first hit at a position;
+    // ties keep insertion order.
+    auto pos = std::find_if(commands_.begin(), commands_.end(), [&](const Command &c) {
+        return c.phrase.size() < phrase.size();
+    });
+    commands_.insert(pos, std::move(cmd));
+}
+
+namespace {
+// Letters and digits of alphabetic scripts — the ones that form words
+// separated by spaces. CJK (from U+2E80 up) has no such boundaries.
+bool isWordChar(QChar c) {
+    return c.isLetterOrNumber() && c.unicode() < 0x2E80;
+}
+
+// A phrase that starts (ends) with a word character must not have one
+// right before (after) it: "period" doesn't fire inside "periodic".
+bool atWordBoundaries(const QString &text, qsizetype at, qsizetype len) {
+    const QChar first = text.at(at);
+    const QChar last = text.at(at + len - 1);
+    if (isWordChar(first) && at > 0 && isWordChar(text.at(at - 1))) return false;
+    if (isWordChar(last) && at + len < text.size() && isWordChar(text.at(at + len))) {
+        return false;
+    }
+    return true;
+}
+} // namespace
+
+QStringList CommandMap::apply(const QString &text) const {
+    static const QString before = QStringLiteral("，,");
+    static const QString after = QStringLiteral("，,。.！!？?");
+    if (commands_.isEmpty()) return {text};
+
+    QStringList out{QString()};
+    qsizetype i = 0;
+    while (i < text.size()) {
+        const Command *hit = nullptr;
+        for (const auto &c : commands_) {
+            if (QStringView(text).mid(i).startsWith(c.phrase, Qt::CaseInsensitive) &&
+                atWordBoundaries(text, i, c.phrase.size())) {
+                hit = &c;
+                break;
+            }
+        }
+        if (!hit) {
+            out.last() += text.at(i++);
+            continue;
+        }
+        i += hit->phrase.size();
+        if (i < text.size() && after.contains(text.at(i))) ++i;
+        QString &cur = out.last();
+        if (!cur.isEmpty() && before.contains(cur.back())) cur.chop(1);
+        switch (hit->action) {
+        case Action::Insert: cur += hit->text; break;
+        case Action::Newline: cur += QLatin1Char('\n'); break;
+        case Action::Commit: out.append(QString()); break;
+        }
+    }
+    return out;
+}
+
+} // namespace postprocess
@@ -0,0 +1,84 @@
+#pragma once
+#include <QList>
+#include <QString>
+#include <QStringList>
+#include <functional>
+
+struct OverlayConfig;
+
+/// Ordered text transforms applied to each final segment before it is
+/// committed. Partials skip the pipeline — they're superseded anyway and
+/// sit on the ~20 Hz preedit path.
+///
+/// Configured as a comma-separated list, applied left to right:
+///   [Asr]
+///   PostProcess = spoken_newline, collapse_spaces, capitalize_first
+///
+/// Built-ins (see `postprocess::byName`):
+///   strip_trailing_punct — drop trailing ，。！？ etc. (also enabled by the
+///                          legacy RemoveTrailingPunctuation flag)
+///   capitalize_first     — upper-case the first letter of the segment
+///   spoken_newline       — "换行" / "new line" → "\n"
+///   collapse_spaces      — squeeze runs of spaces / tabs into one space
+namespace postprocess {
+
+using Transform = std::function<QString(const QString &)>;
+
+/// Built-in transform for `name`; an empty function when unknown.
+Transform byName(const QString &name);
+
+class Pipeline {
+public:
+    /// Reads `[Asr] PostProcess` and the legacy RemoveTrailingPunctuation
+    /// flag (appended last unless already listed). Unknown names are
+    /// logged and skipped.
+    static Pipeline fromConfig(const OverlayConfig &cfg);
+
+    /// Extension point for transforms that aren't simple built-ins.
+    void append(Transform t) { steps_.append(std::move(t)); }
+    QString apply(const QString &text) const;
+
+private:
+    QList<Transform> steps_;
+};
+
+/// Spoken commands: phrases cut out of a final and replaced by an action.
+/// Opt-in — empty unless the config has a `[SpokenCommands]` section,
+/// one phrase per key:
+///   [SpokenCommands]
+///   句号 = insert:。
+///   问号 = insert:？
+///   换行 = newline
+///   发送 = commit              ; commit what we have, keep recording
+///
+/// Runs after the Pipeline, on the processed final. Matching scans left to
+/// right and takes the longest phrase at each position, so "新段落" wins
+/// over "段落" when both are defined. A comma before the phrase and one
+/// punctuation mark after it (ITN likes to add both) go with it. Latin
+/// letters match case-insensitively and only as whole words ("period"
+/// leaves "periodic" alone); CJK phrases match anywhere.
+class CommandMap {
+public:
+    enum class Action { Insert, Newline, Commit };
+
+    /// Reads `[SpokenCommands]`. Unknown actions are logged and skipped.
+    static CommandMap fromConfig(const OverlayConfig &cfg);
+
+    void add(const QString &phrase, Action action, const QString &text = {});
+    bool isEmpty() const { return commands_.isEmpty(); }
+
+    /// `text` with every phrase applied, split at Commit actions: one
+    /// element when there is nothing to commit early, otherwise the
+    /// caller commits each element but the last as it goes.
+    QStringList apply(const QString &text) const;
+
+private:
+    struct Command {
+        QString phrase;
+        Action action;
+        QString text;  // Insert only
+    };
+    QList<Command> commands_;  // longest phrase first
+};
+
+} // namespace postprocess
@@ -0,0 +1,145 @@
+#include "SelfCheck.h"
+
+#include "Config.h"
+#include "asr/AsrBackendFactory.h"
+#include "audio/AudioCapture.h"
+
+#include <QEventLoop>
+#include <QFileInfo>
+#include <QTimer>
+
+namespace selfcheck {
+namespace {
+// Covers pa_simple_new plus the first 40 ms read; warm-up isn't required,
+// a zero-padded chunk already proves the source opened.
+constexpr int kAudioTimeoutMs = 5'000;
+// Slightly above VolcengineBackend's own 10 s handshake timeout so its
+// error message wins over ours.
+constexpr int kAsrTimeoutMs = 12'000;
+
+struct Report {
+    QTextStream &out;
+    bool failed = false;
+
+    void line(const char *status, const char *step, const QString &detail) {
+        out << QStringLiteral("%1  %2  %3")
+                   .arg(QString::fromLatin1(status), -4)
+                   .arg(QString::fromLatin1(step), -7)
+                   .arg(detail)
+            << "\n";
+        out.flush();
+    }
+    void pass(const char *step, const QString &detail) { line("PASS", step, detail); }
+    void skip(const char *step, const QString &detail) { line("SKIP", step, detail); }
+    void fail(const char *step, const QString &detail) {
+        failed = true;
+        line("FAIL", step, detail);
+    }
+};
+
+/// Spin the event loop until `loop.quit()` or `timeoutMs`. False on timeout.
+bool waitFor(QEventLoop &loop, int timeoutMs) {
+    bool timedOut = false;
+    QTimer::singleShot(timeoutMs, &loop, [&]() {
+        timedOut = true;
+        loop.quit();
+    });
+    loop.exec();
+    return !timedOut;
+}
+
+void checkAudio(Report &report, const OverlayConfig &cfg, const PcmFormat &format) {
+    AudioCapture audio;
+    audio.setServer(cfg.str(QStringLiteral("Audio"), QStringLiteral("Server")));
+    audio.setDevice(cfg.str(QStringLiteral("Audio"), QStringLiteral("Device")));
+    audio.setSource(
+        AudioCapture::sourceFromString(cfg.str(QStringLiteral("Audio"), QStringLiteral("Source"))));
+    audio.setFormat(format);
+
+    QEventLoop loop;
+    QString error;
+    bool gotPcm = false;
+    QObject::connect(&audio, &AudioCapture::pcm, &loop, [&]() {
+        gotPcm = true;
+        loop.quit();
+    }, Qt::QueuedConnection);
+    QObject::connect(&audio, &AudioCapture::error, &loop, [&](const QString &msg) {
+        error = msg;
+        loop.quit();
+    }, Qt::QueuedConnection);
+    audio.start();
+    waitFor(loop, kAudioTimeoutMs);
+    audio.stop();
+
+    const auto info = audio.streamInfo();
+    const QString device = info.device.isEmpty() ? QStringLiteral("(default)") : info.device;
+    if (gotPcm) {
+        report.pass("audio", QStringLiteral("%1, %2 Hz, %3 ch")
+                                 .arg(device)
+                                 .arg(info.sampleRate)
+                                 .arg(info.channels));
+    } else if (!error.isEmpty()) {
+        report.fail("audio", QStringLiteral("%1: %2").arg(device, error));
+    } else {
+        report.fail("audio", QStringLiteral("%1: no audio within %2 s")
+                                 .arg(device)
+                                 .arg(kAudioTimeoutMs / 1000));
+    }
+}
+
+void checkAsr(Report &report, const QString &name, AsrBackend &backend) {
+    QEventLoop loop;
+    QString error;
+    bool connected = false;
+    QObject::connect(&backend, &AsrBackend::connected, &loop, [&]() {
+        connected = true;
+        loop.quit();
+    });
+    QObject::connect(&backend, &AsrBackend::error, &loop, [&](const QString &msg) {
+        error = msg;
+        loop.quit();
+    });
+    backend.start();
+    if (!waitFor(loop, kAsrTimeoutMs) && error.isEmpty()) {
+        error = QStringLiteral("no response within %1 s").arg(kAsrTimeoutMs / 1000);
+    }
+    backend.cancel();
+
+    if (connected) report.pass("asr", QStringLiteral("%1: handshake ok").arg(name));
+    else report.fail("asr", QStringLiteral("%1: %2").arg(name, error));
+}
+
+} // namespace
+
+int run(QTextStream &out) {
+    Report report{out};
+
+    const QString path = OverlayConfig::configFilePath();
+    const OverlayConfig cfg = OverlayConfig::load().resolved();
+    {
+        QString detail = path;
+        if (!cfg.activeProfile.isEmpty()) detail += QStringLiteral(" (profile %1)").arg(cfg.activeProfile);
+        if (QFileInfo::exists(path)) report.pass("config", detail);
+        else report.fail("config", detail + QStringLiteral(": not found"));
+    }
+
+    auto backend = asr::create(cfg);
+    if (!backend) {
+        report.fail("backend", QStringLiteral("%1: unknown backend or missing credentials")
+                                   .arg(cfg.backend));
+    } else if (!AudioCapture::supports(backend->pcmFormat())) {
+        report.fail("backend", QStringLiteral("%1: unsupported PCM format").arg(cfg.backend));
+    } else {
+        report.pass("backend", cfg.backend);
+    }
+
+    const PcmFormat format = backend ? backend->pcmFormat() : PcmFormat{};
+    checkAudio(report, cfg, format);
+
+    if (backend) checkAsr(report, cfg.backend, *backend);
+    else report.skip("asr", QStringLiteral("no backend"));
+
+    return report.failed ? 1 : 0;
+}
+
+} // namespace selfcheck
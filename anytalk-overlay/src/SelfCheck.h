@@ -0,0 +1,14 @@
+#pragma once
+#include <QTextStream>
+
+namespace selfcheck {
+
+/// `anytalk-overlay --check`: load the config, open the configured audio
+/// source until the first chunk arrives, then connect the ASR backend and
+/// hang up as soon as the handshake succeeds. Prints one PASS / FAIL / SKIP
+/// line per step to `out`; credentials never appear. Returns a process
+/// exit code — 0 only when every step passed. Needs a running event loop
+/// (QApplication constructed), not a registered bus name.
+int run(QTextStream &out);
+
+} // namespace selfcheck
@@ -0,0 +1,70 @@
+#include "TranscriptLog.h"
+#include "Config.h"
+#include "Logging.h"
+
+#include <QDate>
+#include <QDateTime>
+#include <QDebug>
+#include <QDir>
+#include <QFile>
+#include <QFileInfo>
+#include <QJsonDocument>
+#include <QJsonObject>
+#include <QRegularExpression>
+#include <utility>
+
+namespace {
+constexpr const char *kSuffix = ".jsonl";
+} // namespace
+
+std::unique_ptr<TranscriptLog> TranscriptLog::fromConfig(const OverlayConfig &cfg) {
+    QString dir = cfg.str(QStringLiteral("Overlay"), QStringLiteral("TranscriptDir")).trimmed();
+    if (dir.isEmpty()) return nullptr;
+    if (dir.startsWith(QLatin1String("~/"))) dir = QDir::homePath() + dir.mid(1);
+    const int keepDays =
+        cfg.intIn(QStringLiteral("Overlay"), QStringLiteral("TranscriptKeepDays"), 0, 0, 36500);
+    auto log = std::make_unique<TranscriptLog>(dir, keepDays);
+    log->prune();
+    qInfo().noquote() << "TranscriptLog: appending commits to" << dir;
+    return log;
+}
+
+TranscriptLog::TranscriptLog(QString dir, int keepDays)
+    : dir_(std::move(dir)), keepDays_(keepDays) {}
+
+void TranscriptLog::append(const QString &text) {
+    if (text.isEmpty()) return;
+    if (!QDir().mkpath(dir_)) {
+        qWarning().noquote() << "TranscriptLog: cannot create" << dir_;
+        return;
+    }
+    const QDateTime now = QDateTime::currentDateTime();
+    QFile f(dir_ + QLatin1Char('/') + now.date().toString(Qt::ISODate) +
+            QLatin1String(kSuffix));
+    if (!logging::openPrivate(f, QIODevice::WriteOnly | QIODevice::Append)) {
+        qWarning().noquote() << "TranscriptLog: cannot open" << f.fileName() << "—"
+                             << f.errorString();
+        return;
+    }
+    QJsonObject o;
+    o.insert(QStringLiteral("ts"), now.toString(Qt::ISODateWithMs));
+    o.insert(QStringLiteral("text"), text);
+    f.write(QJsonDocument(o).toJson(QJsonDocument::Compact) + '\n');
+}
+
+void TranscriptLog::prune() const {
+    if (keepDays_ <= 0) return;
+    const QDate cutoff = QDate::currentDate().addDays(-keepDays_);
+    static const QRegularExpression name(QStringLiteral("^(\\d{4}-\\d{2}-\\d{2})\\.jsonl$"));
+    const QDir dir(dir_);
+    const auto files = dir.entryList({QStringLiteral("*") + QLatin1String(kSuffix)}, QDir::Files);
+    for (const auto &file : files) {
+        // Only our own daily files; anything else in the directory stays.
+        const auto m = name.match(file);
+        if (!m.hasMatch()) continue;
+        const QDate day = QDate::fromString(m.captured(1), Qt::ISODate);
+        if (day.isValid() && day < cutoff && dir.remove(file)) {
+            qInfo().noquote() << "TranscriptLog: pruned" << file;
+        }
+    }
+}
@@ -0,0 +1,31 @@
+#pragma once
+#include <QString>
+#include <memory>
+
+struct OverlayConfig;
+
+/// Opt-in local history of everything dictated. Enabled only by
+/// `[Overlay] TranscriptDir` — unset means nothing is ever written.
+/// Each committed text is appended as one JSON line
+///   {"ts":"2026-10-16T09:12:03.481+08:00","text":"..."}
+/// to `<dir>/YYYY-MM-DD.jsonl` (local date), owner-only (0600). Daily
+/// files older than `[Overlay] TranscriptKeepDays` days are deleted when
+/// the overlay starts; 0 keeps everything.
+///
+/// Appends are synchronous: one short line per commit, and the overlay
+/// may _Exit right after a commit, which would lose a queued write.
+class TranscriptLog {
+public:
+    /// nullptr unless `[Overlay] TranscriptDir` is set. Prunes old files.
+    static std::unique_ptr<TranscriptLog> fromConfig(const OverlayConfig &cfg);
+
+    TranscriptLog(QString dir, int keepDays);
+
+    void append(const QString &text);
+
+private:
+    void prune() const;
+
+    QString dir_;
+    int keepDays_ = 0;
+};
@@ -1,7 +1,10 @@
 #pragma once
+#include "audio/PcmFormat.h"
+
 #include <QByteArray>
 #include <QObject>
 #include <QString>
+#include <QStringList>
 
 /// Abstract ASR engine. Concrete backends (Volcengine, OpenAI, local
 /// whisper.cpp, …) implement this. AsrController owns one instance, drives
@@ -17,10 +20,35 @@ public:
     explicit AsrBackend(QObject *parent = nullptr) : QObject(parent) {}
     ~AsrBackend() override = default;
 
+    /// PCM this backend wants. AsrController hands it to AudioCapture
+    /// between sessions (the sound server resamples) and refuses a backend
+    /// whose format the capture path can't produce.
+    virtual PcmFormat pcmFormat() const { return {}; }
+
+    /// Recognition languages (BCP 47 codes). Backends without language
+    /// selection keep the defaults: nothing supported, nothing selected.
+    virtual QStringList supportedLanguages() const { return {}; }
+    virtual QStringList languages() const { return {}; }
+    /// Select languages for subsequent sessions. Returns the accepted
+    /// subset; when it's empty the selection is unchanged.
+    virtual QStringList setLanguages(const QStringList &langs) {
+        Q_UNUSED(langs);
+        return {};
+    }
+
+    /// Audio chunks this backend discarded in the current (or last)
+    /// session — e.g. while its connection was down. Reset by start().
+    virtual int droppedChunks() const { return 0; }
+
+    /// False when nothing comes back until the last frame is in (e.g. a
+    /// non-streaming endpoint); the controller reports "processing"
+    /// as soon as recording stops so the UI can show the wait.
+    virtual bool streamsResults() const { return true; }
+
     /// Begin a new recognition session.
     virtual void start() = 0;
 
-    /// Submit a 16-bit LE 16 kHz mono PCM chunk.
+    /// Submit a PCM chunk in pcmFormat().
     virtual void pushPcm(const QByteArray &chunk) = 0;
 
     /// Signal end-of-speech. Backend should drain any pending finals
@@ -35,6 +63,9 @@ signals:
     void partial(const QString &text);
     /// A stable transcript segment. May fire multiple times in a session.
     void final_(const QString &text);
+    /// Optional: audio time range (ms from session start) of the final_
+    /// emitted right after it. Backends without timing never emit.
+    void finalTiming(qint64 startMs, qint64 endMs);
     /// Human-readable error. Backend is back to idle after this.
     void error(const QString &message);
     /// Connection ready / first frame of the session can flow.
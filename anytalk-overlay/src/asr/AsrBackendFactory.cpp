@@ -1,11 +1,81 @@
 #include "AsrBackendFactory.h"
 #include "Config.h"
+#include "Logging.h"
 #include "VolcengineBackend.h"
 
 #include <QDebug>
+#include <QSslCertificate>
 
 namespace asr {
 
+namespace {
+// RFC 9110 token characters.
+bool isHeaderName(const QString &name) {
+    static const QString extra = QStringLiteral("!#$%&'*+-.^_`|~");
+    if (name.isEmpty()) return false;
+    for (const QChar c : name) {
+        if (c.unicode() > 0x7f || !(c.isLetterOrNumber() || extra.contains(c))) return false;
+    }
+    return true;
+}
+
+// Visible ASCII plus space and tab; anything else (CR/LF above all) could
+// split the request.
+bool isHeaderValue(const QString &value) {
+    for (const QChar c : value) {
+        const ushort u = c.unicode();
+        if (u != '\t' && (u < 0x20 || u > 0x7e)) return false;
+    }
+    return true;
+}
+
+// Headers that carry credentials: Authorization and names ending in
+// -Token / -Key / -Auth / -Secret. Routing values like a region stay
+// readable in the logs.
+bool isAuthHeader(const QString &name) {
+    if (name.compare(QLatin1String("Authorization"), Qt::CaseInsensitive) == 0 ||
+        name.compare(QLatin1String("Proxy-Authorization"), Qt::CaseInsensitive) == 0) {
+        return true;
+    }
+    for (const char *suffix : {"-Token", "-Key", "-Auth", "-Secret"}) {
+        if (name.endsWith(QLatin1String(suffix), Qt::CaseInsensitive)) return true;
+    }
+    return false;
+}
+
+// `[VolcengineHeaders]` Name = value. The X-Api-* headers the endpoint
+// requires, and the WebSocket handshake's own, can't be overridden.
+QList<QPair<QByteArray, QByteArray>> extraHeaders(const OverlayConfig &cfg) {
+    static const QString prefix = QStringLiteral("VolcengineHeaders/");
+    QList<QPair<QByteArray, QByteArray>> out;
+    QStringList names;
+    for (auto it = cfg.backendOptions.constBegin(); it != cfg.backendOptions.constEnd(); ++it) {
+        if (!it.key().startsWith(prefix)) continue;
+        const QString name = it.key().mid(prefix.size());
+        const QString value = it.value().toString();
+        if (name.startsWith(QLatin1String("X-Api-"), Qt::CaseInsensitive) ||
+            name.startsWith(QLatin1String("Sec-WebSocket-"), Qt::CaseInsensitive) ||
+            name.compare(QLatin1String("Host"), Qt::CaseInsensitive) == 0) {
+            qWarning() << "asr::create: [VolcengineHeaders]" << name << "is reserved — skipped";
+            continue;
+        }
+        if (!isHeaderName(name) || !isHeaderValue(value)) {
+            qWarning() << "asr::create: [VolcengineHeaders]" << name
+                       << "is not a valid header name / value — skipped";
+            continue;
+        }
+        if (isAuthHeader(name)) logging::addSecret(value);
+        out.append({name.toLatin1(), value.toLatin1()});
+        names.append(name);
+    }
+    if (!names.isEmpty()) {
+        names.sort();
+        qInfo() << "asr::create: extra request headers" << names;
+    }
+    return out;
+}
+} // namespace
+
 std::unique_ptr<AsrBackend> create(const OverlayConfig &cfg, QObject *parent) {
     if (cfg.backend == QLatin1String("volcengine")) {
         VolcengineBackend::Settings s;
@@ -18,11 +88,50 @@ std::unique_ptr<AsrBackend> create(const OverlayConfig &cfg, QObject *parent) {
         if (!mode.isEmpty()) s.mode = mode;
         s.enableNonstream = cfg.boolean(QStringLiteral("Volcengine"),
                                          QStringLiteral("EnableNonstream"), false);
+        const auto langs = cfg.str(QStringLiteral("Volcengine"), QStringLiteral("Language"))
+                               .split(QLatin1Char(','), Qt::SkipEmptyParts);
+        if (!langs.isEmpty()) {
+            s.languages.clear();
+            for (const auto &lang : langs) s.languages.append(lang.trimmed());
+        }
+        s.useVad = cfg.boolean(QStringLiteral("Volcengine"), QStringLiteral("UseVad"), true);
+        s.maxFrameBytes = cfg.intIn(QStringLiteral("Volcengine"), QStringLiteral("MaxFrameBytes"),
+                                    static_cast<int>(s.maxFrameBytes), 64 * 1024,
+                                    64 * 1024 * 1024);
+        s.connectRetries = cfg.intIn(QStringLiteral("Volcengine"),
+                                     QStringLiteral("ConnectRetries"), s.connectRetries, 0, 3);
+        // [Volcengine] SampleRate: 16000 (default) or 8000.
+        const auto rate = cfg.str(QStringLiteral("Volcengine"), QStringLiteral("SampleRate"));
+        if (rate == QLatin1String("8000")) {
+            s.format.sampleRate = 8000;
+        } else if (!rate.isEmpty() && rate != QLatin1String("16000")) {
+            qWarning() << "asr::create: unsupported [Volcengine] SampleRate" << rate
+                       << "— expected 8000 or 16000; using 16000";
+        }
+
+        // Private roots, e.g. a TLS-intercepting corporate proxy. The
+        // environment wins so a one-off run doesn't need a config edit.
+        QString caBundle = qEnvironmentVariable("ANYTALK_CA_BUNDLE");
+        if (caBundle.isEmpty()) {
+            caBundle = cfg.str(QStringLiteral("Volcengine"), QStringLiteral("CaBundle"));
+        }
+        if (!caBundle.isEmpty()) {
+            s.extraCaCertificates = QSslCertificate::fromPath(caBundle, QSsl::Pem);
+            if (s.extraCaCertificates.isEmpty()) {
+                qWarning() << "asr::create: no PEM certificates in CA bundle" << caBundle;
+            }
+        }
+        s.lastFrameCarriesAudio = cfg.boolean(QStringLiteral("Volcengine"),
+                                              QStringLiteral("LastFrameCarriesAudio"), false);
+        s.extraHeaders = extraHeaders(cfg);
 
         if (s.appId.isEmpty() || s.accessToken.isEmpty()) {
             qWarning() << "asr::create: Volcengine credentials missing — open SettingsDialog.";
             return nullptr;
         }
+        // The token travels in a request header; keep it out of any log
+        // line, including Qt's own network debug output.
+        logging::addSecret(s.accessToken);
         auto backend = std::make_unique<VolcengineBackend>(s, parent);
         return backend;
     }
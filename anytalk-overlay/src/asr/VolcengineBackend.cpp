@@ -3,10 +3,12 @@
 #include <QDebug>
 #include <QMetaEnum>
 #include <QNetworkRequest>
+#include <QSslConfiguration>
 #include <QSslError>
 #include <QTimer>
 #include <QUrl>
 #include <QWebSocket>
+#include <utility>
 
 namespace {
 // 10 s — fail fast on bad token / DNS, survive Wi-Fi roaming.
@@ -31,7 +33,8 @@ QString pathForMode(const QString &mode) {
 } // namespace
 
 VolcengineBackend::VolcengineBackend(Settings settings, QObject *parent)
-    : AsrBackend(parent), settings_(std::move(settings)) {
+    : AsrBackend(parent), settings_(std::move(settings)),
+      frameLog_(volcengine::FrameLog::fromEnvironment()) {
     handshakeTimer_.setSingleShot(true);
     connect(&handshakeTimer_, &QTimer::timeout,
             this, &VolcengineBackend::onHandshakeTimeout);
@@ -41,6 +44,10 @@ VolcengineBackend::~VolcengineBackend() = default;
 
 void VolcengineBackend::openWebSocket() {
     ws_ = std::make_unique<QWebSocket>();
+    // Without a cap QWebSocket reassembles a message of any size in memory
+    // before we see it; oversized messages close the socket instead.
+    ws_->setMaxAllowedIncomingFrameSize(settings_.maxFrameBytes);
+    ws_->setMaxAllowedIncomingMessageSize(settings_.maxFrameBytes);
     connect(ws_.get(), &QWebSocket::connected, this, &VolcengineBackend::onWsConnected);
     connect(ws_.get(), &QWebSocket::binaryMessageReceived, this, &VolcengineBackend::onWsBinary);
     connect(ws_.get(), &QWebSocket::errorOccurred, this, &VolcengineBackend::onWsError);
@@ -50,6 +57,12 @@ void VolcengineBackend::openWebSocket() {
     connect(ws_.get(), &QWebSocket::sslErrors, this, &VolcengineBackend::onWsSslErrors);
     connect(ws_.get(), &QWebSocket::stateChanged,
             this, &VolcengineBackend::onWsStateChanged);
+    sslFailed_ = false;
+    if (!settings_.extraCaCertificates.isEmpty()) {
+        QSslConfiguration ssl = QSslConfiguration::defaultConfiguration();
+        ssl.addCaCertificates(settings_.extraCaCertificates);
+        ws_->setSslConfiguration(ssl);
+    }
 
     QNetworkRequest req(QUrl(QStringLiteral("wss://%1%2").arg(kHost, pathForMode(settings_.mode))));
     req.setRawHeader("X-Api-App-Key", settings_.appId.toUtf8());
@@ -57,17 +70,40 @@ void VolcengineBackend::openWebSocket() {
     req.setRawHeader("X-Api-Resource-Id", settings_.resourceId.toUtf8());
     req.setRawHeader("X-Api-Connect-Id",
                      QUuid::createUuid().toString(QUuid::WithoutBraces).toUtf8());
+    for (const auto &[name, value] : std::as_const(settings_.extraHeaders)) {
+        req.setRawHeader(name, value);
+    }
     ws_->open(req);
 
     handshakeTimer_.start(kHandshakeTimeoutMs);
 }
 
+QStringList VolcengineBackend::setLanguages(const QStringList &langs) {
+    QStringList accepted;
+    for (const auto &lang : langs) {
+        if (volcengine::knownLanguages().contains(lang) && !accepted.contains(lang)) {
+            accepted.append(lang);
+        }
+    }
+    // Read when the next session connects; the live one keeps its request.
+    if (!accepted.isEmpty()) settings_.languages = accepted;
+    return accepted;
+}
+
 void VolcengineBackend::start() {
     if (state_ != State::Idle) return;
     parseState_ = {};
     pendingAudio_.clear();
+    heldChunk_.clear();
+    unackedAudio_.clear();
+    serverAcked_ = false;
+    stopRequested_ = false;
+    retriesLeft_ = settings_.connectRetries;
     nextSeq_ = 1;
+    droppedChunks_ = 0;
+    dropLog_.reset();
     state_ = State::Connecting;
+    if (frameLog_) frameLog_->begin(settings_.mode);
     openWebSocket();
 }
 
@@ -75,28 +111,52 @@ void VolcengineBackend::pushPcm(const QByteArray &chunk) {
     if (state_ == State::Connecting) {
         // Buffer for onWsConnected() to flush. Cap so a stuck handshake
         // (network down) can't grow the buffer unbounded.
-        constexpr int kMaxPendingBytes = 16000 * 2 * 10;  // 10s @ 16kHz S16LE
-        if (pendingAudio_.size() < kMaxPendingBytes) {
+        const int maxPendingBytes = settings_.format.bytesFor(10'000);
+        if (pendingAudio_.size() < maxPendingBytes) {
             pendingAudio_.append(chunk);
+        } else {
+            noteDroppedChunk("handshake buffer full");
         }
         return;
     }
     if (state_ != State::Recording) return;
-    if (!ws_ || ws_->state() != QAbstractSocket::ConnectedState) return;
+    if (!ws_ || ws_->state() != QAbstractSocket::ConnectedState) {
+        noteDroppedChunk("socket not connected");
+        return;
+    }
+    QByteArray out = chunk;
+    if (settings_.lastFrameCarriesAudio) {
+        out = std::exchange(heldChunk_, chunk);
+        if (out.isEmpty()) return;
+    }
+    if (!serverAcked_ && unackedAudio_.size() < settings_.format.bytesFor(10'000)) {
+        unackedAudio_.append(out);
+    }
     ws_->sendBinaryMessage(volcengine::buildAudioOnlyRequest(
-        chunk, /*last=*/false, nextSeq_++));
+        out, /*last=*/false, nextSeq_++));
 }
 
 void VolcengineBackend::stop() {
+    if (state_ == State::Connecting) {
+        // Not connected yet — first handshake or a retryConnect() in
+        // flight. onWsConnected() sends LAST once the buffer is flushed.
+        stopRequested_ = true;
+        return;
+    }
     if (state_ != State::Recording) return;
+    sendLastFrame();
+    // Server will deliver one or more responses + close; teardown happens in
+    // onWsDisconnected / on a final response frame (flags & 0x3 == 0x3).
+}
+
+void VolcengineBackend::sendLastFrame() {
     state_ = State::Stopping;
     if (ws_ && ws_->state() == QAbstractSocket::ConnectedState) {
-        // Send a final audio frame with the LAST flag so the server knows to drain.
+        // Send a final audio frame with the LAST flag so the server knows to
+        // drain — empty, or the held-back chunk under lastFrameCarriesAudio.
         ws_->sendBinaryMessage(volcengine::buildAudioOnlyRequest(
-            QByteArray(), /*last=*/true, nextSeq_++));
+            std::exchange(heldChunk_, {}), /*last=*/true, nextSeq_++));
     }
-    // Server will deliver one or more responses + close; teardown happens in
-    // onWsDisconnected / on a final response frame (flags & 0x3 == 0x3).
 }
 
 void VolcengineBackend::cancel() {
@@ -109,26 +169,45 @@ void VolcengineBackend::onWsConnected() {
     if (state_ != State::Connecting) return;
     emit connected();
     state_ = State::Recording;
-    const auto initial = volcengine::buildInitialRequestJson(settings_.mode,
-                                                              settings_.enableNonstream);
+    volcengine::InitialRequest req;
+    req.mode = settings_.mode;
+    req.enableNonstream = settings_.enableNonstream;
+    req.format = settings_.format;
+    req.languages = settings_.languages;
+    req.useVad = settings_.useVad;
+    const auto initial = volcengine::buildInitialRequestJson(req);
     ws_->sendBinaryMessage(volcengine::buildFullClientRequest(initial, nextSeq_++));
     // Flush handshake-buffered audio in 200ms slices — Doubao silently
     // drops audio_only frames much larger than that.
     if (!pendingAudio_.isEmpty()) {
-        constexpr int kFlushSliceBytes = 16000 * 2 * 200 / 1000;  // 200ms @ 16kHz S16LE
-        for (int off = 0; off < pendingAudio_.size(); off += kFlushSliceBytes) {
-            const int len = std::min<int>(kFlushSliceBytes,
+        const int flushSliceBytes = settings_.format.bytesFor(200);
+        for (int off = 0; off < pendingAudio_.size(); off += flushSliceBytes) {
+            const int len = std::min<int>(flushSliceBytes,
                                           pendingAudio_.size() - off);
             ws_->sendBinaryMessage(volcengine::buildAudioOnlyRequest(
                 pendingAudio_.mid(off, len), /*last=*/false, nextSeq_++));
         }
+        if (!serverAcked_) unackedAudio_ = pendingAudio_;
         pendingAudio_.clear();
     }
+    if (std::exchange(stopRequested_, false)) sendLastFrame();
 }
 
 void VolcengineBackend::onWsBinary(const QByteArray &data) {
-    const auto parsed = volcengine::parseServerFrame(data);
+    if (frameLog_) frameLog_->append(data);
+    // Any answer, even an error, means this connection really works;
+    // retrying past this point would only duplicate audio.
+    serverAcked_ = true;
+    unackedAudio_.clear();
+    const auto parsed = volcengine::parseServerFrame(data, settings_.maxFrameBytes);
     if (parsed.kind == volcengine::ParsedFrame::Kind::Error) {
+        qWarning().noquote() << "VolcengineBackend: server error" << parsed.errorCode
+                             << "—" << parsed.errorMessage;
+        if (volcengine::isQuotaExhausted(parsed)) {
+            qWarning() << "VolcengineBackend: quota exhausted, code" << parsed.errorCode;
+            teardown(QStringLiteral("豆包额度已用完，请到火山引擎控制台检查用量或并发配额"));
+            return;
+        }
         const QString msg = parsed.errorMessage.isEmpty() ? QStringLiteral("server error")
                                                           : parsed.errorMessage;
         teardown(msg);
@@ -138,7 +217,11 @@ void VolcengineBackend::onWsBinary(const QByteArray &data) {
 
     const auto asr = volcengine::parseAsrResponse(parsed.jsonText, parseState_, settings_.mode);
     if (asr.partial.has_value()) emit partial(*asr.partial);
-    for (const auto &f : asr.finals) emit final_(f);
+    for (qsizetype i = 0; i < asr.finals.size(); ++i) {
+        const auto &meta = asr.finalMeta.at(i);
+        if (meta.endMs >= 0) emit finalTiming(meta.startMs, meta.endMs);
+        emit final_(asr.finals.at(i));
+    }
 
     if (parsed.isFinalFrame()) {
         // Server side end-of-recognition.
@@ -150,11 +233,18 @@ void VolcengineBackend::onWsError(QAbstractSocket::SocketError err) {
     qWarning().noquote() << "VolcengineBackend: ws error" << enumName(err)
                          << "—" << (ws_ ? ws_->errorString() : QStringLiteral("(no ws)"));
     if (state_ == State::Idle) return;
+    if (sslFailed_) {
+        // A retry would hit the same certificate; say what's actually wrong.
+        teardown(QStringLiteral("TLS 证书校验失败，如经过代理请配置 ANYTALK_CA_BUNDLE 或 [Volcengine] CaBundle"));
+        return;
+    }
+    if (retryConnect(ws_ ? ws_->errorString() : QStringLiteral("WebSocket error"))) return;
     teardown(ws_ ? ws_->errorString() : QStringLiteral("WebSocket error"));
 }
 
 void VolcengineBackend::onWsDisconnected() {
     if (state_ == State::Idle) return;
+    if (retryConnect(QStringLiteral("closed before the first response"))) return;
     // Normal close after the final frame: state already moved through Stopping.
     teardown({});
 }
@@ -166,6 +256,7 @@ void VolcengineBackend::onWsSslErrors(const QList<QSslError> &errors) {
     for (const auto &e : errors) {
         qWarning().noquote() << "VolcengineBackend: ssl error" << e.errorString();
     }
+    if (!errors.isEmpty()) sslFailed_ = true;
 }
 
 void VolcengineBackend::onWsStateChanged(QAbstractSocket::SocketState s) {
@@ -180,24 +271,64 @@ void VolcengineBackend::onHandshakeTimeout() {
              .arg(kHandshakeTimeoutMs / 1000));
 }
 
+void VolcengineBackend::noteDroppedChunk(const char *reason) {
+    ++droppedChunks_;
+    if (!dropLog_.allow()) return;
+    qWarning() << "VolcengineBackend: dropping audio —" << reason
+               << "(" << droppedChunks_ << "chunks so far this session)";
+}
+
+bool VolcengineBackend::retryConnect(const QString &reason) {
+    // Stopping is excluded: the LAST frame was already sent and replaying
+    // it correctly isn't worth the complexity for a session that's ending.
+    if (serverAcked_ || retriesLeft_ <= 0 ||
+        (state_ != State::Connecting && state_ != State::Recording)) {
+        return false;
+    }
+    --retriesLeft_;
+    qWarning().noquote() << "VolcengineBackend: connection failed before the first response ("
+                         << reason << ") — reconnecting," << retriesLeft_ << "retries left";
+    releaseSocket();
+    // Replay everything the dead socket swallowed, ahead of what arrived
+    // since; onWsConnected() flushes it after the new initial request.
+    pendingAudio_.prepend(unackedAudio_);
+    unackedAudio_.clear();
+    parseState_ = {};
+    nextSeq_ = 1;
+    state_ = State::Connecting;
+    openWebSocket();
+    return true;
+}
+
+void VolcengineBackend::releaseSocket() {
+    if (!ws_) return;
+    // Can be called from within a QWebSocket signal slot
+    // (binaryMessageReceived, errorOccurred, disconnected). Destroying
+    // the socket synchronously while Qt's network stack is mid-emit
+    // causes a use-after-free inside QAbstractSocket::canReadNotification
+    // / qopensslbackend (observed: SIGSEGV with bogus vtable pointer).
+    // Detach signals first, then defer destruction to the event loop.
+    QWebSocket *raw = ws_.release();
+    raw->disconnect(this);
+    if (raw->state() != QAbstractSocket::UnconnectedState) raw->close();
+    raw->deleteLater();
+}
+
 void VolcengineBackend::teardown(const QString &errorMessage) {
     handshakeTimer_.stop();
-    if (ws_) {
-        // teardown() can be called from within a QWebSocket signal slot
-        // (binaryMessageReceived, errorOccurred, disconnected). Destroying
-        // the socket synchronously while Qt's network stack is mid-emit
-        // causes a use-after-free inside QAbstractSocket::canReadNotification
-        // / qopensslbackend (observed: SIGSEGV with bogus vtable pointer).
-        // Detach signals first, then defer destruction to the event loop.
-        QWebSocket *raw = ws_.release();
-        raw->disconnect(this);
-        if (raw->state() != QAbstractSocket::UnconnectedState) raw->close();
-        raw->deleteLater();
+    if (frameLog_) frameLog_->finish();
+    if (droppedChunks_ > 0) {
+        qWarning() << "VolcengineBackend: session ended with" << droppedChunks_
+                   << "dropped audio chunks — transcript may have gaps";
     }
+    releaseSocket();
     const bool wasError = !errorMessage.isEmpty();
     state_ = State::Idle;
     parseState_ = {};
     pendingAudio_.clear();
+    heldChunk_.clear();
+    unackedAudio_.clear();
+    stopRequested_ = false;
     if (wasError) emit error(errorMessage);
     else emit finished();
 }
@@ -1,10 +1,14 @@
 #pragma once
 #include "AsrBackend.h"
+#include "Logging.h"
+#include "VolcengineFrameLog.h"
 #include "VolcengineProtocol.h"
 
 #include <QAbstractSocket>
 #include <QByteArray>
 #include <QList>
+#include <QPair>
+#include <QSslCertificate>
 #include <QSslError>
 #include <QString>
 #include <QTimer>
@@ -35,15 +39,49 @@ public:
         // is only supported on the optimized bidi path; the protocol layer
         // gates the JSON insertion to enforce that server-side rule.
         bool enableNonstream = false;
+        // Rate 16000 (default) or 8000; rate / bits / channel of the
+        // initial request are taken from here.
+        PcmFormat format;
+        // `[Volcengine] UseVad`; see InitialRequest::useVad.
+        bool useVad = true;
+        // Largest server frame we accept, in bytes (`[Volcengine]
+        // MaxFrameBytes`). Enforced by QWebSocket before buffering and by
+        // the frame parser.
+        quint32 maxFrameBytes = volcengine::kDefaultMaxPayloadBytes;
+        // `[Volcengine] Language`, comma-separated; see InitialRequest.
+        QStringList languages{QStringLiteral("zh-CN")};
+        // Reconnects allowed when the socket fails before the server's
+        // first frame (`[Volcengine] ConnectRetries`, 0..3).
+        int connectRetries = 1;
+        // Trusted in addition to the system roots (ANYTALK_CA_BUNDLE /
+        // `[Volcengine] CaBundle`, PEM).
+        QList<QSslCertificate> extraCaCertificates;
+        // `[Volcengine] LastFrameCarriesAudio`: hold each live chunk back
+        // by one so stop() can flag the final real chunk as last instead
+        // of sending an empty last frame. Costs one chunk (40 ms) of lag.
+        bool lastFrameCarriesAudio = false;
+        // `[VolcengineHeaders]` Name = value: sent on the upgrade request
+        // after the mandatory X-Api-* headers, e.g. for a gateway in front
+        // of the endpoint. Validated by asr::create().
+        QList<QPair<QByteArray, QByteArray>> extraHeaders;
     };
 
     explicit VolcengineBackend(Settings settings, QObject *parent = nullptr);
     ~VolcengineBackend() override;
 
+    PcmFormat pcmFormat() const override { return settings_.format; }
+    QStringList supportedLanguages() const override { return volcengine::knownLanguages(); }
+    QStringList languages() const override { return settings_.languages; }
+    QStringList setLanguages(const QStringList &langs) override;
     void start() override;
     void pushPcm(const QByteArray &chunk) override;
     void stop() override;
     void cancel() override;
+    int droppedChunks() const override { return droppedChunks_; }
+    bool streamsResults() const override {
+        return settings_.mode == QLatin1String("bidi") ||
+               settings_.mode == QLatin1String("bidi_async");
+    }
 
 private slots:
     void onWsConnected();
@@ -58,8 +96,16 @@ private:
     enum class State { Idle, Connecting, Recording, Stopping };
 
     void openWebSocket();
+    /// Detach and schedule deletion of ws_; safe inside its signal slots.
+    void releaseSocket();
+    /// Enter Stopping and send the LAST frame (carrying heldChunk_).
+    void sendLastFrame();
+    /// Reconnect once more if the session hasn't heard from the server
+    /// yet and retries remain. True if a new connection is under way.
+    bool retryConnect(const QString &reason);
     void resetSession();
     void teardown(const QString &errorMessage);
+    void noteDroppedChunk(const char *reason);
 
     Settings settings_;
     std::unique_ptr<QWebSocket> ws_;
@@ -70,13 +116,36 @@ private:
     // Audio captured during ws handshake; flushed in onWsConnected() so the
     // user's leading words aren't dropped.
     QByteArray pendingAudio_;
+    // The chunk held back under lastFrameCarriesAudio; sent by the next
+    // pushPcm(), or with the LAST flag by stop().
+    QByteArray heldChunk_;
+    // stop() arrived while Connecting (first handshake or a retry);
+    // onWsConnected() sends LAST after flushing pendingAudio_.
+    bool stopRequested_ = false;
+
+    // Until the server's first frame proves the session is alive, keep
+    // what we sent so a retryConnect() can replay it on a new socket.
+    bool serverAcked_ = false;
+    // Set by onWsSslErrors so onWsError can report a certificate problem
+    // as such instead of Qt's generic handshake error.
+    bool sslFailed_ = false;
+    QByteArray unackedAudio_;
+    int retriesLeft_ = 0;
 
     // Per-connection sequence: full client request gets 1, audio frames 2..N.
     // The protocol rejects mixed seq/no-seq frames within one connection.
     qint32 nextSeq_ = 1;
 
+    // Chunks discarded by pushPcm() this session (handshake buffer full,
+    // socket gone). Warned at most once per second, summarized in teardown();
+    // kept until the next start() for droppedChunks().
+    int droppedChunks_ = 0;
+    logging::RateLimit dropLog_{1000};
+
     // QWebSocket has no built-in handshake timeout — a TLS-completed but
     // upgrade-stuck server would hang in Connecting forever. Fires
     // teardown() with a clear error so the UI can recover.
     QTimer handshakeTimer_;
+
+    std::unique_ptr<volcengine::FrameLog> frameLog_;  // null unless ANYTALK_DEBUG_FRAMES_DIR
 };
@@ -0,0 +1,139 @@
+#include "VolcengineFrameLog.h"
+#include "Logging.h"
+#include "VolcengineProtocol.h"
+
+#include <QDataStream>
+#include <QDateTime>
+#include <QDebug>
+#include <QDir>
+#include <QUuid>
+#include <algorithm>
+
+namespace volcengine {
+
+namespace {
+constexpr const char *kEnvVar = "ANYTALK_DEBUG_FRAMES_DIR";
+constexpr quint32 kMagic = 0x41544652; // "ATFR"
+constexpr quint32 kVersion = 1;
+} // namespace
+
+std::unique_ptr<FrameLog> FrameLog::fromEnvironment() {
+    const QString dir = qEnvironmentVariable(kEnvVar);
+    if (dir.isEmpty()) return nullptr;
+    qInfo().noquote() << "FrameLog: dumping server frames to" << dir;
+    return std::make_unique<FrameLog>(dir);
+}
+
+FrameLog::FrameLog(QString dir) : dir_(std::move(dir)) {}
+
+FrameLog::~FrameLog() { finish(); }
+
+void FrameLog::begin(const QString &mode) {
+    finish();
+    if (!QDir().mkpath(dir_)) {
+        qWarning().noquote() << "FrameLog: cannot create" << dir_;
+        return;
+    }
+    file_.setFileName(dir_ + QLatin1Char('/') +
+                      QUuid::createUuid().toString(QUuid::WithoutBraces) +
+                      QStringLiteral(".frames"));
+    if (!logging::openPrivate(file_, QIODevice::WriteOnly | QIODevice::Truncate)) {
+        qWarning().noquote() << "FrameLog: cannot open" << file_.fileName() << "—"
+                             << file_.errorString();
+        return;
+    }
+    startMs_ = QDateTime::currentMSecsSinceEpoch();
+    QDataStream out(&file_);
+    out << kMagic << kVersion << mode;
+}
+
+void FrameLog::append(const QByteArray &frame) {
+    if (!file_.isOpen()) return;
+    QDataStream out(&file_);
+    out << QDateTime::currentMSecsSinceEpoch() - startMs_ << frame;
+}
+
+void FrameLog::finish() {
+    if (!file_.isOpen()) return;
+    file_.close();
+    qInfo().noquote() << "FrameLog: wrote" << file_.fileName();
+}
+
+int replayFrameLog(const QString &path, QTextStream &out) {
+    QFile f(path);
+    if (!f.open(QIODevice::ReadOnly)) {
+        out << "cannot open " << path << ": " << f.errorString() << "\n";
+        return 1;
+    }
+    QDataStream in(&f);
+    quint32 magic = 0, version = 0;
+    QString mode;
+    in >> magic >> version >> mode;
+    if (in.status() != QDataStream::Ok || magic != kMagic || version != kVersion) {
+        out << path << ": not an anytalk frame log\n";
+        return 1;
+    }
+    out << "mode " << mode << "\n";
+
+    AsrParseState state;
+    int frames = 0;
+    while (!in.atEnd()) {
+        qint64 ms = 0;
+        QByteArray data;
+        in >> ms >> data;
+        if (in.status() != QDataStream::Ok) {
+            out << "truncated record after " << frames << " frames\n";
+            return 1;
+        }
+        ++frames;
+        const QString at = QStringLiteral("+%1ms ").arg(ms, 6);
+        const auto parsed = parseServerFrame(data);
+        switch (parsed.kind) {
+        case ParsedFrame::Kind::Error:
+            out << at << "error " << parsed.errorCode << " " << parsed.errorMessage << "\n";
+            break;
+        case ParsedFrame::Kind::Unknown:
+            out << at << "unknown frame (" << data.size() << " bytes)\n";
+            break;
+        case ParsedFrame::Kind::Response: {
+            const auto asr = parseAsrResponse(parsed.jsonText, state, mode);
+            if (asr.partial) out << at << "partial " << *asr.partial << "\n";
+            for (const auto &text : asr.finals) out << at << "final   " << text << "\n";
+            if (parsed.isFinalFrame()) out << at << "end of recognition\n";
+            break;
+        }
+        }
+    }
+    out << frames << " frames\n";
+    return 0;
+}
+
+int checkFrameLog(const QString &path, const QString &goldenPath, QTextStream &out) {
+    QString actual;
+    {
+        QTextStream buf(&actual);
+        if (const int rc = replayFrameLog(path, buf); rc != 0) {
+            buf.flush();
+            out << actual;
+            return rc;
+        }
+    }
+    QFile golden(goldenPath);
+    if (!golden.open(QIODevice::ReadOnly | QIODevice::Text)) {
+        out << "cannot open " << goldenPath << ": " << golden.errorString() << "\n";
+        return 1;
+    }
+    const QStringList want = QString::fromUtf8(golden.readAll()).split(QLatin1Char('\n'));
+    const QStringList got = actual.split(QLatin1Char('\n'));
+    for (qsizetype i = 0; i < std::max(want.size(), got.size()); ++i) {
+        if (want.value(i) == got.value(i)) continue;
+        out << path << ": differs from " << goldenPath << " at line " << i + 1 << "\n"
+            << "  expected: " << want.value(i) << "\n"
+            << "  actual:   " << got.value(i) << "\n";
+        return 1;
+    }
+    out << path << ": matches " << goldenPath << "\n";
+    return 0;
+}
+
+} // namespace volcengine
@@ -0,0 +1,45 @@
+#pragma once
+#include <QByteArray>
+#include <QFile>
+#include <QString>
+#include <QTextStream>
+#include <memory>
+
+namespace volcengine {
+
+/// Opt-in capture of every raw binary frame the server sends, so a wrong
+/// transcript can be reproduced offline with `anytalk-overlay --replay`.
+/// One `<dir>/<uuid>.frames` per session, enabled only by
+/// ANYTALK_DEBUG_FRAMES_DIR. The file holds the session mode (the parser
+/// behaves differently per mode) followed by (ms since begin, frame)
+/// records, QDataStream-encoded.
+class FrameLog {
+public:
+    /// nullptr unless ANYTALK_DEBUG_FRAMES_DIR is set to a non-empty path.
+    static std::unique_ptr<FrameLog> fromEnvironment();
+
+    explicit FrameLog(QString dir);
+    ~FrameLog();
+
+    void begin(const QString &mode);
+    void append(const QByteArray &frame);
+    void finish();
+
+private:
+    QString dir_;
+    QFile file_;
+    qint64 startMs_ = 0;
+};
+
+/// Feed a `.frames` file back through parseServerFrame / parseAsrResponse
+/// and print the resulting partial / final / error sequence to `out`.
+/// Returns a process exit code (0 on success).
+int replayFrameLog(const QString &path, QTextStream &out);
+
+/// replayFrameLog() checked against a golden file holding its expected
+/// output (`--replay f --expect golden`). Prints the first differing line
+/// and returns 1 on mismatch, 0 when identical — a regression test for the
+/// parser that needs neither network nor microphone.
+int checkFrameLog(const QString &path, const QString &goldenPath, QTextStream &out);
+
+} // namespace volcengine
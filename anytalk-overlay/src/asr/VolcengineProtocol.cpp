@@ -1,5 +1,6 @@
 #include "VolcengineProtocol.h"
 
+#include <QDebug>
 #include <QJsonArray>
 #include <QJsonDocument>
 #include <QJsonObject>
@@ -15,8 +16,11 @@ constexpr quint8 kMsgFullClientReq = 0b0001;
 constexpr quint8 kMsgAudioOnly     = 0b0010;
 constexpr quint8 kMsgFullServerRsp = 0b1001;
 constexpr quint8 kMsgErrorResp     = 0b1111;
+// Gateway codes sent with "quota exceeded for types: ..." (concurrency
+// or usage allowance).
+constexpr quint32 kQuotaErrorCodes[] = {45000292};
 constexpr quint8 kFlagPosSeq       = 0b0001;  // intermediate frame
-constexpr quint8 kFlagNegWithSeq   = 0b0011;  // last frame, seq negated
+constexpr quint8 kFlagNegWithSeq   = kFlagLastBit | kFlagPosSeq;  // last frame, seq negated
 constexpr quint8 kSerJson          = 0b0001;
 constexpr quint8 kSerNone          = 0b0000;
 constexpr quint8 kCompressionNone  = 0b0000;
@@ -36,6 +40,15 @@ QByteArray u32be(quint32 n) {
     qToBigEndian(n, b.data());
     return b;
 }
+
+void warnUnexpectedShape(AsrParseState &state, const QString &what, const QJsonObject &obj) {
+    if (state.warnedShape) return;
+    state.warnedShape = true;
+    QString keys = obj.keys().join(QLatin1Char(','));
+    if (keys.size() > 120) keys = keys.left(120) + QStringLiteral("…");
+    qWarning().noquote() << "VolcengineProtocol: response without" << what
+                         << "— keys present: [" + keys + "]";
+}
 } // namespace
 
 QByteArray buildFullClientRequest(const QByteArray &json, qint32 seq) {
@@ -70,7 +83,7 @@ QByteArray buildAudioOnlyRequest(const QByteArray &pcm, bool last, qint32 seq) {
     return out;
 }
 
-ParsedFrame parseServerFrame(const QByteArray &data) {
+ParsedFrame parseServerFrame(const QByteArray &data, quint32 maxPayloadBytes) {
     ParsedFrame f;
     if (data.size() < 4) return f;
 
@@ -86,7 +99,13 @@ ParsedFrame parseServerFrame(const QByteArray &data) {
     if (messageType == kMsgFullServerRsp) {
         const auto payloadSize =
             qFromBigEndian<quint32>(reinterpret_cast<const uchar *>(data.constData() + 8));
-        if (data.size() < static_cast<int>(12 + payloadSize)) return f;
+        if (payloadSize > maxPayloadBytes) {
+            qWarning() << "VolcengineProtocol: response declares" << payloadSize
+                       << "payload bytes, over the" << maxPayloadBytes << "cap; dropped";
+            return f;
+        }
+        // qsizetype: 12 + a quint32 near 4 GiB must not wrap.
+        if (data.size() < 12 + static_cast<qsizetype>(payloadSize)) return f;
         f.kind = ParsedFrame::Kind::Response;
         f.jsonText = data.mid(12, payloadSize);
         return f;
@@ -97,7 +116,12 @@ ParsedFrame parseServerFrame(const QByteArray &data) {
             qFromBigEndian<quint32>(reinterpret_cast<const uchar *>(data.constData() + 4));
         const auto msgSize =
             qFromBigEndian<quint32>(reinterpret_cast<const uchar *>(data.constData() + 8));
-        if (data.size() < static_cast<int>(12 + msgSize)) return f;
+        if (msgSize > maxPayloadBytes) {
+            qWarning() << "VolcengineProtocol: error frame declares" << msgSize
+                       << "message bytes, over the" << maxPayloadBytes << "cap; dropped";
+            return f;
+        }
+        if (data.size() < 12 + static_cast<qsizetype>(msgSize)) return f;
         f.kind = ParsedFrame::Kind::Error;
         f.errorCode = code;
         f.errorMessage = QString::fromUtf8(data.mid(12, msgSize));
@@ -106,11 +130,35 @@ ParsedFrame parseServerFrame(const QByteArray &data) {
     return f;
 }
 
-QByteArray buildInitialRequestJson(const QString &mode, bool enableNonstream) {
+bool isQuotaExhausted(const ParsedFrame &frame) {
+    if (frame.kind != ParsedFrame::Kind::Error) return false;
+    for (const quint32 code : kQuotaErrorCodes) {
+        if (frame.errorCode == code) return true;
+    }
+    if (!frame.errorMessage.contains(QLatin1String("quota"), Qt::CaseInsensitive)) return false;
+    // Fallback for a code we don't know yet; worth adding to the list.
+    qWarning() << "VolcengineProtocol: treating error" << frame.errorCode
+               << "as quota exhausted by its message";
+    return true;
+}
+
+const QStringList &knownLanguages() {
+    static const QStringList langs{
+        QStringLiteral("zh-CN"), QStringLiteral("en-US"), QStringLiteral("ja-JP"),
+        QStringLiteral("ko-KR"), QStringLiteral("es-MX"), QStringLiteral("fr-FR"),
+        QStringLiteral("de-DE"), QStringLiteral("pt-BR"), QStringLiteral("id-ID"),
+        QStringLiteral("ru-RU"),
+    };
+    return langs;
+}
+
+QByteArray buildInitialRequestJson(const InitialRequest &req) {
+    const QString &mode = req.mode;
     const bool isNoStream = (mode == QLatin1String("nostream"));
     QJsonObject audio{
-        {"format", "pcm"}, {"rate", 16000}, {"bits", 16}, {"channel", 1}};
-    if (isNoStream) audio.insert("language", "zh-CN");
+        {"format", "pcm"}, {"rate", req.format.sampleRate},
+        {"bits", req.format.bitsPerSample}, {"channel", req.format.channels}};
+    if (isNoStream && req.languages.size() == 1) audio.insert("language", req.languages.first());
 
     QJsonObject request{
         {"model_name", "bigmodel"},
@@ -120,12 +168,12 @@ QByteArray buildInitialRequestJson(const QString &mode, bool enableNonstream) {
         {"enable_word", false},
         {"res_type", "full"},
         {"nbest", 1},
-        {"use_vad", true},
+        {"use_vad", req.useVad},
     };
     // Two-pass: bidi delivers realtime partials, then nostream re-recognizes
     // each VAD-segmented utterance for higher final accuracy. Server enforces
     // bidi-only; we still gate here so older modes aren't silently changed.
-    if (enableNonstream && mode == QLatin1String("bidi")) {
+    if (req.enableNonstream && mode == QLatin1String("bidi")) {
         request.insert("enable_nonstream", true);
     }
 
@@ -147,8 +195,16 @@ AsrParsed parseAsrResponse(const QByteArray &jsonBytes, AsrParseState &state,
 
     const auto root = doc.object();
     const auto resultVal = root.value(QStringLiteral("result"));
-    if (!resultVal.isObject()) return result;
+    if (!resultVal.isObject()) {
+        warnUnexpectedShape(state, QStringLiteral("a result object"), root);
+        return result;
+    }
     const auto resultObj = resultVal.toObject();
+    if (!resultObj.contains(QStringLiteral("utterances")) &&
+        !resultObj.contains(QStringLiteral("text"))) {
+        warnUnexpectedShape(state, QStringLiteral("result.utterances / result.text"), resultObj);
+        return result;
+    }
 
     auto trim = [](const QString &s) { return s.trimmed(); };
 
@@ -167,7 +223,10 @@ AsrParsed parseAsrResponse(const QByteArray &jsonBytes, AsrParseState &state,
                 if (endTime <= state.lastCommittedEndTime) continue;
                 const QString text = trim(u.value(QStringLiteral("text")).toString());
                 if (text.isEmpty()) continue;
+                const auto startVal = u.value(QStringLiteral("start_time"));
                 result.finals.append(text);
+                result.finalMeta.append(
+                    {startVal.isUndefined() ? -1 : startVal.toVariant().toLongLong(), endTime});
                 state.lastCommittedEndTime = endTime;
             }
 
@@ -199,6 +258,8 @@ AsrParsed parseAsrResponse(const QByteArray &jsonBytes, AsrParseState &state,
     } else if (fullText != state.lastFullText) {
         result.finals.append(fullText);
     }
+    // No utterance timing in the text-only shape.
+    result.finalMeta.resize(result.finals.size());
     state.lastFullText = fullText;
     return result;
 }
@@ -1,5 +1,8 @@
 #pragma once
+#include "audio/PcmFormat.h"
+
 #include <QByteArray>
+#include <QList>
 #include <QString>
 #include <QStringList>
 #include <optional>
@@ -12,6 +15,12 @@ namespace volcengine {
 QByteArray buildFullClientRequest(const QByteArray &json, qint32 seq);
 QByteArray buildAudioOnlyRequest(const QByteArray &pcm, bool last, qint32 seq);
 
+// Header flag bit marking the last frame of a stream. Both NEG_SEQUENCE
+// (0b0010) and NEG_WITH_SEQUENCE (0b0011) carry it; test the bit rather
+// than the whole nibble so extra bits the server may add don't hide the
+// terminal response.
+constexpr quint8 kFlagLastBit = 0b0010;
+
 struct ParsedFrame {
     enum class Kind { Unknown, Response, Error };
     Kind kind = Kind::Unknown;
@@ -19,29 +28,70 @@ struct ParsedFrame {
     QByteArray jsonText;        // when kind == Response
     quint32 errorCode = 0;       // when kind == Error
     QString errorMessage;        // when kind == Error
-    bool isFinalFrame() const { return (flags & 0x3) == 0x3; } // 0b0011
+    bool isFinalFrame() const { return (flags & kFlagLastBit) != 0; }
 };
 
-ParsedFrame parseServerFrame(const QByteArray &data);
+// Default cap on a server frame's declared payload. Real responses are a
+// few KB of JSON; anything near this is a malformed or hostile frame.
+constexpr quint32 kDefaultMaxPayloadBytes = 1024 * 1024;
+
+/// Frames whose declared payload exceeds `maxPayloadBytes`, or exceeds
+/// what `data` holds, come back as Kind::Unknown (logged when oversized).
+ParsedFrame parseServerFrame(const QByteArray &data,
+                             quint32 maxPayloadBytes = kDefaultMaxPayloadBytes);
 
-/// Build the initial FULL_CLIENT_REQUEST JSON. `enableNonstream` toggles
-/// Doubao's two-pass recognition (partials over bidi + finals re-run via
-/// nostream). Server-side: only honored when mode == "bidi"; ignored
-/// silently elsewhere per docs.
-QByteArray buildInitialRequestJson(const QString &mode, bool enableNonstream = false);
+/// True when an Error frame reports the account's quota / concurrency
+/// allowance as used up; retrying won't help until the user tops up or
+/// waits. Decided by the server's error code; a frame with an unknown code
+/// still counts if its message mentions "quota" ("quota exceeded for
+/// types: ..."), which is logged with the code.
+bool isQuotaExhausted(const ParsedFrame &frame);
+
+/// Language codes the bigmodel endpoint accepts as `audio.language`.
+const QStringList &knownLanguages();
+
+/// Everything the initial FULL_CLIENT_REQUEST depends on.
+struct InitialRequest {
+    QString mode = QStringLiteral("bidi_async");
+    // Doubao's two-pass recognition (partials over bidi + finals re-run via
+    // nostream). Server-side: only honored when mode == "bidi"; ignored
+    // silently elsewhere per docs.
+    bool enableNonstream = false;
+    PcmFormat format;  // must be what pushPcm() sends
+    // Server-side VAD splits the stream into utterances. Off, the only
+    // endpoint is our last-frame flag — one utterance per push-to-talk.
+    bool useVad = true;
+    // Only nostream takes a language hint: one entry pins it, several
+    // (code-switching) leave it unset so the model detects per utterance.
+    QStringList languages{QStringLiteral("zh-CN")};
+};
+
+/// Build the initial FULL_CLIENT_REQUEST JSON.
+QByteArray buildInitialRequestJson(const InitialRequest &req);
 
 struct AsrParseState {
     qint64 lastCommittedEndTime = -1;
     QString lastFullText;
+    // Unexpected-shape warning already logged this session.
+    bool warnedShape = false;
+};
+
+struct FinalMeta {
+    qint64 startMs = -1;  // utterance audio range; -1 when the server didn't say
+    qint64 endMs = -1;
 };
 
 struct AsrParsed {
     std::optional<QString> partial;
     QStringList finals;
+    QList<FinalMeta> finalMeta;  // parallel to `finals`
 };
 
 /// Parse a server JSON payload, extracting partial / finals.
 /// Stateful: caller persists `state` across messages within a session.
+/// A payload with neither `result.utterances` nor `result.text` yields
+/// nothing; the first such payload per session is logged with its keys so
+/// a server-side format change doesn't look like silence.
 AsrParsed parseAsrResponse(const QByteArray &json, AsrParseState &state, const QString &mode);
 
 } // namespace volcengine
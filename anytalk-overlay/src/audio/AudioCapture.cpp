@@ -1,9 +1,22 @@
 #include "AudioCapture.h"
+#include "Wav.h"
 
 #include <QDebug>
+#include <QElapsedTimer>
+#include <QFile>
+#include <QtEndian>
+#include <pulse/channelmap.h>
 #include <pulse/error.h>
 #include <pulse/simple.h>
+#include <algorithm>
 #include <cmath>
+#include <cerrno>
+#include <cstdlib>
+#include <cstring>
+#include <fcntl.h>
+#include <poll.h>
+#include <sys/stat.h>
+#include <unistd.h>
 
 AudioCapture::AudioCapture(QObject *parent) : QObject(parent) {}
 
@@ -38,9 +51,18 @@ void AudioCapture::teardownStream() {
         pa_simple_free(static_cast<pa_simple *>(pa_));
         pa_ = nullptr;
     }
+    {
+        QMutexLocker lock(&infoMutex_);
+        info_.open = false;
+    }
     warmedUp_.store(false, std::memory_order_release);
 }
 
+AudioCapture::StreamInfo AudioCapture::streamInfo() const {
+    QMutexLocker lock(&infoMutex_);
+    return info_;
+}
+
 bool AudioCapture::start() {
     // Idempotent: if a previous start() left a live stream, just flip the
     // forwarding flag.
@@ -58,15 +80,30 @@ bool AudioCapture::start() {
     // overlaps with both PA open and PA warm-up.
     running_.store(true, std::memory_order_release);
     active_.store(true, std::memory_order_release);
-    thread_ = QThread::create([this] { captureLoop(); });
+    const bool monitor = source_ == Source::Monitor;
+    QByteArray device = device_.toUtf8();
+    if (device.isEmpty() && monitor) device = QByteArrayLiteral("@DEFAULT_MONITOR@");
+    // The high-pass exists for mic rumble; program audio doesn't need it.
+    const double highPassHz = monitor ? 0.0 : highPassHz_;
+    StreamOptions opts{server_.toUtf8(), device,   downmix_, highPassHz,
+                       simulateFile_,      fifoFile_, format_};
+    thread_ = QThread::create([this, opts] { captureLoop(opts); });
     thread_->setObjectName(QStringLiteral("anytalk-capture"));
     thread_->start();
     return true;
 }
 
+void AudioCapture::setInputGainDb(double db) {
+    gainDb_.store(db, std::memory_order_relaxed);
+    gainFactor_.store(std::pow(10.0, db / 20.0), std::memory_order_relaxed);
+}
+
 void AudioCapture::stop() {
-    active_.store(false, std::memory_order_release);
+    // Keep active_ set while the read thread winds down: the pa_simple_read
+    // in flight completes (≤ one chunk) and is still emitted, so the last
+    // syllable before Stop reaches the backend instead of being dropped.
     teardownStream();
+    active_.store(false, std::memory_order_release);
 }
 
 // captureLoop runs on a dedicated QThread (created by start()). AudioCapture
@@ -79,35 +116,82 @@ void AudioCapture::stop() {
 // connect() calls in AsrController::applyConfig pin Qt::QueuedConnection
 // explicitly so the thread contract is visible at the call site, not just
 // implied by AutoConnection.
-void AudioCapture::captureLoop() {
+void AudioCapture::captureLoop(const StreamOptions &opts) {
+    if (!opts.simulateFile.isEmpty()) {
+        simulateLoop(opts);
+        return;
+    }
+    if (!opts.fifoFile.isEmpty()) {
+        fifoLoop(opts);
+        return;
+    }
+    const QByteArray &device = opts.device;
+    // Left / Right need both sides from PA; Average lets PA mix to mono.
+    const int channels = opts.downmix == Downmix::Average ? 1 : 2;
+
     pa_sample_spec spec{};
     spec.format = PA_SAMPLE_S16LE;
-    spec.rate = kSampleRate;
-    spec.channels = 1;
+    spec.rate = static_cast<uint32_t>(opts.format.sampleRate);
+    spec.channels = static_cast<uint8_t>(channels);
+
+    pa_channel_map map{};
+    if (channels == 2) pa_channel_map_init_stereo(&map);
 
     pa_buffer_attr attr{};
     attr.maxlength = static_cast<uint32_t>(-1);
     attr.tlength = static_cast<uint32_t>(-1);
     attr.prebuf = static_cast<uint32_t>(-1);
     attr.minreq = static_cast<uint32_t>(-1);
-    attr.fragsize = kChunkBytes;
+    attr.fragsize = static_cast<uint32_t>(opts.format.bytesFor(kChunkMs) * channels);
 
+    QByteArray server = opts.server;
+    auto openStream = [&](int *paErr) {
+        return pa_simple_new(server.isEmpty() ? nullptr : server.constData(), "anytalk",
+                             PA_STREAM_RECORD, device.isEmpty() ? nullptr : device.constData(),
+                             "Voice Input", &spec, channels == 2 ? &map : nullptr, &attr, paErr);
+    };
     int paErr = 0;
-    auto *pa = pa_simple_new(nullptr, "anytalk", PA_STREAM_RECORD, nullptr,
-                              "Voice Input", &spec, nullptr, &attr, &paErr);
+    auto *pa = openStream(&paErr);
+    if (!pa && !server.isEmpty()) {
+        qWarning() << "AudioCapture: server" << server << "unavailable:" << pa_strerror(paErr)
+                   << "— falling back to the default server";
+        server.clear();
+        pa = openStream(&paErr);
+    }
     if (!pa) {
-        qWarning() << "AudioCapture: pa_simple_new failed:" << pa_strerror(paErr);
-        emit error(QStringLiteral("麦克风不可用，请检查 PulseAudio/PipeWire 或音频设备"));
+        qWarning() << "AudioCapture: pa_simple_new failed:" << pa_strerror(paErr)
+                   << "device:" << (device.isEmpty() ? QByteArray("(default)") : device);
+        // libpulse-simple can't enumerate sources, so an unknown [Audio]
+        // Device name only shows up here as a connect failure.
+        emit error(device.isEmpty()
+                       ? QStringLiteral("麦克风不可用，请检查 PulseAudio/PipeWire 或音频设备")
+                       : QStringLiteral("麦克风 %1 不可用，请检查 [Audio] Device 配置")
+                             .arg(QString::fromUtf8(device)));
         running_.store(false, std::memory_order_release);
         return;
     }
     pa_ = pa;
+    qInfo() << "AudioCapture: opened server" << (server.isEmpty() ? QByteArray("(default)") : server)
+            << "device" << (device.isEmpty() ? QByteArray("(default)") : device);
+    {
+        QMutexLocker lock(&infoMutex_);
+        info_.open = true;
+        info_.server = QString::fromUtf8(server);
+        info_.device = QString::fromUtf8(device);
+        info_.sampleRate = static_cast<int>(spec.rate);
+        info_.channels = channels;
+        info_.sampleFormat = QStringLiteral("s16le");
+        info_.fragmentBytes = static_cast<int>(attr.fragsize);
+        info_.downmix = downmixToString(opts.downmix);
+        info_.highPassHz = opts.highPassHz;
+    }
 
-    QByteArray buf;
-    buf.resize(kChunkBytes);
+    ChunkProcessor proc(opts);
+    QByteArray raw;
+    raw.resize(opts.format.bytesFor(kChunkMs) * channels);
     while (running_.load(std::memory_order_acquire)) {
         int err = 0;
-        if (pa_simple_read(pa, buf.data(), buf.size(), &err) < 0) {
+        if (pa_simple_read(pa, raw.data(), raw.size(), &err) < 0) {
             qWarning() << "AudioCapture: pa_simple_read failed:" << pa_strerror(err);
             if (active_.load(std::memory_order_acquire)) {
                 emit error(QStringLiteral("音频读取失败"));
@@ -115,16 +199,258 @@ void AudioCapture::captureLoop() {
             running_.store(false, std::memory_order_release);
             break;
         }
-        const double rms = computeRms(buf);
-        if (!warmedUp_.load(std::memory_order_acquire) && rms > 1e-4) {
-            warmedUp_.store(true, std::memory_order_release);
-            emit warmedUp();
+        processChunk(raw, channels, proc);
+    }
+}
+
+// File-fed stand-in for captureLoop: same thread, same processing, paced
+// at real time so the backend and the UI see what a live mic would give
+// them. After the file runs out it keeps feeding silence, like a mic in a
+// quiet room, until the session stops.
+void AudioCapture::simulateLoop(const StreamOptions &opts) {
+    const auto file = wav::readMono(opts.simulateFile, opts.format.sampleRate);
+    if (!file.ok) {
+        qWarning().noquote() << "AudioCapture: cannot simulate from" << opts.simulateFile
+                             << "—" << file.error;
+        emit error(QStringLiteral("模拟音频文件不可用：%1").arg(file.error));
+        running_.store(false, std::memory_order_release);
+        return;
+    }
+    {
+        QMutexLocker lock(&infoMutex_);
+        info_.open = true;
+        info_.device = QStringLiteral("simulate:") + opts.simulateFile;
+        info_.sampleRate = opts.format.sampleRate;
+        info_.channels = 1;
+        info_.sampleFormat = QStringLiteral("s16le");
+        info_.fragmentBytes = opts.format.bytesFor(kChunkMs);
+        info_.downmix = downmixToString(Downmix::Average);
+        info_.highPassHz = opts.highPassHz;
+    }
+
+    ChunkProcessor proc(opts);
+    const int bytes = opts.format.bytesFor(kChunkMs);
+    const QByteArray silence(bytes, '\0');
+    qsizetype offset = 0;
+    QElapsedTimer clock;
+    clock.start();
+    for (qint64 n = 0; running_.load(std::memory_order_acquire); ++n) {
+        const qint64 dueMs = n * kChunkMs;
+        const qint64 waitMs = dueMs - clock.elapsed();
+        if (waitMs > 0) QThread::msleep(static_cast<unsigned long>(waitMs));
+        QByteArray chunk = file.pcm.mid(offset, bytes);
+        offset += chunk.size();
+        if (chunk.size() < bytes) chunk.append(silence.left(bytes - chunk.size()));
+        processChunk(chunk, 1, proc);
+    }
+}
+
+void AudioCapture::fifoLoop(const StreamOptions &opts) {
+    const QByteArray path = QFile::encodeName(opts.fifoFile);
+    struct stat st {};
+    if (::stat(path.constData(), &st) != 0 || !S_ISFIFO(st.st_mode)) {
+        qWarning() << "AudioCapture:" << opts.fifoFile << "is not a named pipe";
+        emit error(QStringLiteral("音频管道 %1 不存在或不是 FIFO").arg(opts.fifoFile));
+        running_.store(false, std::memory_order_release);
+        return;
+    }
+    {
+        QMutexLocker lock(&infoMutex_);
+        info_.open = true;
+        info_.device = QStringLiteral("fifo:") + opts.fifoFile;
+        info_.sampleRate = opts.format.sampleRate;
+        info_.channels = 1;
+        info_.sampleFormat = QStringLiteral("s16le");
+        info_.fragmentBytes = opts.format.bytesFor(kChunkMs);
+        info_.downmix = downmixToString(Downmix::Average);
+        info_.highPassHz = opts.highPassHz;
+    }
+
+    ChunkProcessor proc(opts);
+    const int bytes = opts.format.bytesFor(kChunkMs);
+    QByteArray chunk;
+    QByteArray buf(bytes, Qt::Uninitialized);
+    const QByteArray silence(bytes, '\0');
+    int fd = -1;
+    // Until a writer delivers data (and again after it leaves), feed
+    // digital silence at real time: to the stall watchdog and the server
+    // an idle pipe then looks like a quiet mic, not a dead one.
+    bool writerActive = false;
+    QElapsedTimer idle;
+    idle.start();
+    qint64 silenceChunks = 0;
+    // Non-blocking open + poll with a short timeout: a blocking open or
+    // read would wait for a writer forever and stop() could never join us.
+    while (running_.load(std::memory_order_acquire)) {
+        if (!writerActive) {
+            for (const qint64 due = idle.elapsed() / kChunkMs; silenceChunks < due;
+                 ++silenceChunks) {
+                processChunk(silence, 1, proc);
+            }
+        }
+        if (fd < 0) {
+            fd = ::open(path.constData(), O_RDONLY | O_NONBLOCK | O_CLOEXEC);
+            if (fd < 0) {
+                qWarning() << "AudioCapture: cannot open" << opts.fifoFile << ":"
+                           << strerror(errno);
+                emit error(QStringLiteral("音频管道 %1 无法打开").arg(opts.fifoFile));
+                running_.store(false, std::memory_order_release);
+                return;
+            }
+        }
+        pollfd pfd{fd, POLLIN, 0};
+        const int ready = ::poll(&pfd, 1, kChunkMs);
+        if (ready <= 0) continue;  // timeout or EINTR; re-check running_
+        const ssize_t n = ::read(fd, buf.data(), bytes - chunk.size());
+        if (n > 0) {
+            writerActive = true;
+            chunk.append(buf.constData(), n);
+            if (chunk.size() == bytes) {
+                processChunk(chunk, 1, proc);
+                chunk.clear();
+            }
+            continue;
+        }
+        if (n < 0 && (errno == EAGAIN || errno == EINTR)) continue;
+        // EOF: the writer went away. Reopen for the next one; the pause
+        // keeps a pipe with no writer from spinning on POLLHUP.
+        qInfo() << "AudioCapture: FIFO writer closed; waiting for the next one";
+        ::close(fd);
+        fd = -1;
+        chunk.clear();
+        writerActive = false;
+        idle.start();
+        silenceChunks = 0;
+        QThread::msleep(kChunkMs);
+    }
+    if (fd >= 0) ::close(fd);
+}
+
+AudioCapture::ChunkProcessor::ChunkProcessor(const StreamOptions &opts)
+    : downmix(opts.downmix) {
+    if (opts.highPassHz > 0.0) highPass.emplace(opts.highPassHz, opts.format.sampleRate);
+}
+
+void AudioCapture::processChunk(const QByteArray &raw, int channels, ChunkProcessor &proc) {
+    // Clipping detection: judged over ~1 s so one plosive doesn't count.
+    constexpr int kClipWindowChunks = 25;        // 25 × 40 ms
+    constexpr double kClipWarnRatio = 0.01;
+    constexpr qint64 kClipWarnIntervalMs = 5000;
+
+    QByteArray buf = downmixChunk(raw, channels, proc.downmix);
+    const double gain = gainFactor_.load(std::memory_order_relaxed);
+    if (gain != 1.0) applyGain(buf, gain);
+    // Count before filtering: clipping is a property of the input gain.
+    proc.windowClipped += countClipped(buf);
+    proc.windowSamples += buf.size() / 2;
+    if (++proc.windowChunks >= kClipWindowChunks) {
+        const double ratio = proc.windowSamples > 0
+                                 ? static_cast<double>(proc.windowClipped) / proc.windowSamples
+                                 : 0.0;
+        clipRatio_.store(ratio, std::memory_order_relaxed);
+        if (ratio > kClipWarnRatio && active_.load(std::memory_order_acquire) &&
+            (!proc.sinceClipWarn.isValid() ||
+             proc.sinceClipWarn.elapsed() >= kClipWarnIntervalMs)) {
+            proc.sinceClipWarn.start();
+            qWarning() << "AudioCapture: input clipping —" << qRound(ratio * 100.0)
+                       << "% of samples at full scale; lower the mic gain";
+            emit clipping(ratio);
         }
-        if (active_.load(std::memory_order_acquire)) {
-            emit pcm(buf);
-            emit level(rms);
+        proc.windowSamples = proc.windowClipped = 0;
+        proc.windowChunks = 0;
+    }
+    if (proc.highPass) proc.highPass->process(buf);
+    const double rms = computeRms(buf);
+    if (!warmedUp_.load(std::memory_order_acquire) && rms > 1e-4) {
+        warmedUp_.store(true, std::memory_order_release);
+        emit warmedUp();
+    }
+    if (active_.load(std::memory_order_acquire)) {
+        emit pcm(buf);
+        emit level(rms);
+    }
+}
+
+AudioCapture::Source AudioCapture::sourceFromString(const QString &s, bool *ok) {
+    const QString v = s.trimmed().toLower();
+    if (ok) *ok = v.isEmpty() || v == QLatin1String("mic") || v == QLatin1String("monitor");
+    return v == QLatin1String("monitor") ? Source::Monitor : Source::Mic;
+}
+
+AudioCapture::Downmix AudioCapture::downmixFromString(const QString &s, bool *ok) {
+    const QString v = s.trimmed().toLower();
+    if (ok) *ok = true;
+    if (v == QLatin1String("left") || v == QLatin1String("first") ||
+        v == QLatin1String("index:0")) {
+        return Downmix::Left;
+    }
+    if (v == QLatin1String("right") || v == QLatin1String("index:1")) return Downmix::Right;
+    if (v == QLatin1String("max")) return Downmix::Max;
+    if (ok) *ok = v.isEmpty() || v == QLatin1String("average");
+    return Downmix::Average;
+}
+
+QString AudioCapture::downmixToString(Downmix mode) {
+    switch (mode) {
+    case Downmix::Left: return QStringLiteral("left");
+    case Downmix::Right: return QStringLiteral("right");
+    case Downmix::Max: return QStringLiteral("max");
+    case Downmix::Average: break;
+    }
+    return QStringLiteral("average");
+}
+
+QByteArray AudioCapture::downmixChunk(const QByteArray &interleaved, int channels,
+                                      Downmix mode) {
+    if (channels <= 1) return interleaved;
+    const qsizetype frames = interleaved.size() / (2 * channels);
+    QByteArray out(frames * 2, Qt::Uninitialized);
+    const char *in = interleaved.constData();
+    for (qsizetype i = 0; i < frames; ++i) {
+        const char *frame = in + i * channels * 2;
+        qint16 v = 0;
+        switch (mode) {
+        case Downmix::Left:
+            v = qFromLittleEndian<qint16>(frame);
+            break;
+        case Downmix::Right:
+            v = qFromLittleEndian<qint16>(frame + 2);
+            break;
+        case Downmix::Average: {
+            int sum = 0;
+            for (int c = 0; c < channels; ++c) sum += qFromLittleEndian<qint16>(frame + c * 2);
+            v = static_cast<qint16>(sum / channels);
+            break;
         }
+        case Downmix::Max:
+            for (int c = 0; c < channels; ++c) {
+                const qint16 s = qFromLittleEndian<qint16>(frame + c * 2);
+                if (std::abs(s) > std::abs(v)) v = s;
+            }
+            break;
+        }
+        qToLittleEndian(v, out.data() + i * 2);
+    }
+    return out;
+}
+
+void AudioCapture::applyGain(QByteArray &pcm16le, double factor) {
+    const qsizetype n = pcm16le.size() / 2;
+    auto *data = reinterpret_cast<int16_t *>(pcm16le.data());
+    for (qsizetype i = 0; i < n; ++i) {
+        const double v = std::round(static_cast<double>(data[i]) * factor);
+        data[i] = static_cast<int16_t>(std::clamp(v, -32768.0, 32767.0));
+    }
+}
+
+int AudioCapture::countClipped(const QByteArray &pcm16le) {
+    const qsizetype n = pcm16le.size() / 2;
+    const auto *data = reinterpret_cast<const int16_t *>(pcm16le.constData());
+    int clipped = 0;
+    for (qsizetype i = 0; i < n; ++i) {
+        if (data[i] >= 32767 || data[i] <= -32767) ++clipped;
     }
+    return clipped;
 }
 
 double AudioCapture::computeRms(const QByteArray &pcm16le) {
@@ -1,18 +1,67 @@
 #pragma once
+#include "HighPassFilter.h"
+#include "PcmFormat.h"
+
 #include <QByteArray>
+#include <QElapsedTimer>
+#include <QMutex>
 #include <QObject>
+#include <QString>
 #include <QThread>
 #include <atomic>
+#include <optional>
 
-/// 16-bit little-endian, 16 kHz, mono PCM capture.
-/// Emits 40 ms (1280 byte / 640 sample) chunks; emits an RMS level
-/// estimate (~25 Hz). Backed by libpulse-simple on Linux.
+/// 16-bit little-endian mono PCM capture, 16 kHz unless the backend asks
+/// otherwise (setFormat). Emits 40 ms chunks (1280 bytes at 16 kHz);
+/// emits an RMS level estimate (~25 Hz). Backed by libpulse-simple on Linux.
 /// One PA stream per object lifetime: start() opens, stop()/dtor release.
 class AudioCapture : public QObject {
     Q_OBJECT
 public:
-    static constexpr int kSampleRate = 16000;
-    static constexpr int kChunkBytes = 1280; // 40 ms @ 16 kHz mono S16LE
+    static constexpr int kChunkMs = 40;
+
+    /// Whether the capture path can produce `format`: S16LE mono, any rate
+    /// the sound server will resample to.
+    static bool supports(const PcmFormat &format) {
+        return format.bitsPerSample == 16 && format.channels == 1 && format.sampleRate >= 8000;
+    }
+
+    /// How a multi-channel source is reduced to the mono stream we emit.
+    /// Average lets PulseAudio mix (mono sample spec); Left / Right open a
+    /// stereo stream and keep one side — for interfaces that put the mic on
+    /// one input of a stereo pair, where averaging halves the level. Max
+    /// opens stereo too and keeps whichever side is louder per sample, for
+    /// a voice that moves between two mics.
+    enum class Downmix { Average, Left, Right, Max };
+
+    /// What to record when no explicit device is set: the default source
+    /// (microphone) or the monitor of the default sink — i.e. whatever is
+    /// playing, for transcribing a meeting.
+    enum class Source { Mic, Monitor };
+
+    /// Parses `[Audio] Source` / SetAudioSource; unknown strings → Mic.
+    static Source sourceFromString(const QString &s, bool *ok = nullptr);
+
+    /// Parses the `[Audio] Downmix` value; unknown strings → Average.
+    /// `first` and `index:0` / `index:1` name Left / Right; other indices
+    /// are out of range for the stereo stream we open and are rejected.
+    static Downmix downmixFromString(const QString &s, bool *ok = nullptr);
+    static QString downmixToString(Downmix mode);
+
+    /// What the current (or last) PA stream was actually opened with.
+    /// libpulse-simple can't report the resolved source name, so `device`
+    /// is what we asked for; empty = server default.
+    struct StreamInfo {
+        bool open = false;
+        QString server;            // empty = default server
+        QString device;
+        int sampleRate = 0;
+        int channels = 0;          // as opened; the emitted stream is mono
+        QString sampleFormat;      // "s16le"
+        int fragmentBytes = 0;     // requested PA fragsize
+        QString downmix;
+        double highPassHz = 0.0;   // 0 = off
+    };
 
     explicit AudioCapture(QObject *parent = nullptr);
     ~AudioCapture() override;
@@ -23,16 +72,77 @@ public:
     bool start();
 
     /// Tear down the PA stream and the read thread so the kernel actually
-    /// releases the source (and any BT SCO link). Safe to call multiple
-    /// times; safe to call from the destructor.
+    /// releases the source (and any BT SCO link). The chunk being read when
+    /// stop() is called is still emitted before the thread exits. Safe to
+    /// call multiple times; safe to call from the destructor.
     void stop();
 
+    /// PulseAudio source name to record from (`pactl list short sources`);
+    /// empty selects the server default. Takes effect on the next start() —
+    /// a live stream keeps its source until it's torn down, so switching
+    /// mid-session would cost a PA reopen + warm-up gap we don't want.
+    void setDevice(const QString &name) { device_ = name; }
+    QString device() const { return device_; }
+
+    /// Sound server to connect to, in PA's server string syntax
+    /// (`unix:/run/user/1000/pulse/native`, `tcp:host`); empty lets
+    /// libpulse pick ($PULSE_SERVER, then the session default). When the
+    /// named server refuses the connection we warn and fall back to the
+    /// default. Latched at the next start().
+    void setServer(const QString &server) { server_ = server; }
+
+    /// Output format (the backend's AsrBackend::pcmFormat(); must pass
+    /// supports()). PA resamples from the source's native rate. Latched at
+    /// the next start().
+    void setFormat(const PcmFormat &format) { format_ = format; }
+    const PcmFormat &format() const { return format_; }
+
+    /// Mic or sink monitor. Monitor resolves to PulseAudio's
+    /// `@DEFAULT_MONITOR@` (PipeWire's pulse server honours it too) and
+    /// skips the mic-oriented high-pass. An explicit setDevice() wins.
+    /// Latched at the next start().
+    void setSource(Source source) { source_ = source; }
+    Source source() const { return source_; }
+
+    /// Channel policy; latched at the next start() like setDevice().
+    void setDownmix(Downmix mode) { downmix_ = mode; }
+
+    /// High-pass cutoff in Hz applied to the mono stream; 0 disables.
+    /// Latched at the next start().
+    void setHighPassHz(double hz) { highPassHz_ = hz; }
+
+    /// Replace the microphone with a WAV file at format().sampleRate, fed through the same
+    /// processing at real time (then silence). Empty = live capture.
+    /// Latched at the next start(); for `--simulate-audio`.
+    void setSimulatedSource(const QString &wavPath) { simulateFile_ = wavPath; }
+
+    /// Read raw S16LE mono PCM at format().sampleRate from a named pipe
+    /// instead of PulseAudio, for when another process owns the mic
+    /// (ANYTALK_AUDIO_FIFO). Chunked like live capture and paced by the
+    /// writer; when the writer closes, the pipe is reopened for the next
+    /// one. While no writer is connected, silence is produced at real time
+    /// so the session sees a quiet mic. Empty = live capture. Latched at
+    /// the next start().
+    void setFifoSource(const QString &fifoPath) { fifoFile_ = fifoPath; }
+
+    /// Fixed software gain in dB, applied with saturation right after the
+    /// downmix. Unlike the options above this is read per chunk, so it can
+    /// be nudged mid-session. Any thread.
+    void setInputGainDb(double db);
+    double inputGainDb() const { return gainDb_.load(std::memory_order_relaxed); }
+
     bool isActive() const { return active_.load(std::memory_order_acquire); }
 
     /// True once the underlying PA stream has produced its first non-silent
     /// chunk (i.e. the source has finished its zero-padding ramp-up). Sticky.
     bool isWarmedUp() const { return warmedUp_.load(std::memory_order_acquire); }
 
+    /// Snapshot of the negotiated stream, refreshed on every (re)open.
+    StreamInfo streamInfo() const;
+
+    /// Fraction of full-scale samples over the last ~1 s window, 0..1.
+    double clipRatio() const { return clipRatio_.load(std::memory_order_relaxed); }
+
 signals:
     void pcm(const QByteArray &chunk);
     void level(double rms);  // 0..1
@@ -41,18 +151,67 @@ signals:
     /// controller hold off the "Recording" UI state until the mic is really
     /// awake.
     void warmedUp();
+    /// Sustained clipping while active: more than 1 % of the samples in a
+    /// ~1 s window sat at full scale. At most once per 5 s.
+    void clipping(double ratio);
 
 private:
-    void captureLoop();
+    /// Per-stream parameters, copied into the capture thread at start().
+    struct StreamOptions {
+        QByteArray server;   // empty = libpulse default
+        QByteArray device;   // empty = PA default source
+        Downmix downmix = Downmix::Average;
+        double highPassHz = 0.0;  // 0 = off
+        QString simulateFile;     // non-empty = read this WAV instead of PA
+        QString fifoFile;         // non-empty = read raw PCM from this FIFO
+        PcmFormat format;
+    };
+
+    /// Per-stream processing state, owned by the capture thread. Whatever
+    /// produces raw frames (PA or a file) hands them to processChunk().
+    struct ChunkProcessor {
+        explicit ChunkProcessor(const StreamOptions &opts);
+        Downmix downmix;
+        std::optional<HighPassFilter> highPass;
+        qint64 windowSamples = 0;  // clipping window
+        qint64 windowClipped = 0;
+        int windowChunks = 0;
+        QElapsedTimer sinceClipWarn;
+    };
+
+    void captureLoop(const StreamOptions &opts);
+    void simulateLoop(const StreamOptions &opts);
+    void fifoLoop(const StreamOptions &opts);
+    /// Downmix → gain → clip detection → high-pass → level, then emit.
+    void processChunk(const QByteArray &raw, int channels, ChunkProcessor &proc);
     /// Stop the read thread and release the pa_simple stream. Bounded
     /// wait — leaks the thread + pa_simple if PA is wedged so the caller
     /// (stop() or ~AudioCapture()) doesn't deadlock.
     void teardownStream();
     static double computeRms(const QByteArray &pcm16le);
+    static int countClipped(const QByteArray &pcm16le);
+    /// Multiply S16LE samples by `factor` in place, saturating at full scale.
+    static void applyGain(QByteArray &pcm16le, double factor);
+    /// Reduce interleaved S16LE `channels`-wide frames to mono per `mode`.
+    static QByteArray downmixChunk(const QByteArray &interleaved, int channels,
+                                   Downmix mode);
 
     QThread *thread_ = nullptr;
     std::atomic_bool running_{false};  // thread should keep reading
     std::atomic_bool active_{false};   // forward reads to listeners
     std::atomic_bool warmedUp_{false}; // first non-silent chunk seen, sticky
+    std::atomic<double> clipRatio_{0.0};
+    mutable QMutex infoMutex_;
+    StreamInfo info_;                  // written by the capture thread
+    std::atomic<double> gainDb_{0.0};
+    std::atomic<double> gainFactor_{1.0}; // 10^(gainDb_/20), read per chunk
     void *pa_ = nullptr;               // pa_simple* (kept opaque)
+    QString device_;                   // main thread only; copied into the capture thread
+    QString server_;                   // ditto
+    Downmix downmix_ = Downmix::Average; // ditto
+    double highPassHz_ = 0.0;            // ditto
+    QString simulateFile_;               // ditto
+    QString fifoFile_;                   // ditto
+    PcmFormat format_;                    // ditto
+    Source source_ = Source::Mic;         // ditto
 };
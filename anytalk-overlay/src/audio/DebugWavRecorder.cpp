@@ -0,0 +1,73 @@
+#include "DebugWavRecorder.h"
+#include "Logging.h"
+#include "Wav.h"
+
+#include <QDebug>
+#include <QDir>
+#include <QFileInfo>
+#include <QUuid>
+
+namespace {
+constexpr const char *kEnvVar = "ANYTALK_DEBUG_WAV_DIR";
+// Oldest dumps are deleted once the directory grows past this. ~100 min
+// of 16 kHz S16LE — plenty for a bug report, bounded for a forgotten env var.
+constexpr qint64 kMaxDirBytes = 200LL * 1024 * 1024;
+} // namespace
+
+std::unique_ptr<DebugWavRecorder> DebugWavRecorder::fromEnvironment() {
+    const QString dir = qEnvironmentVariable(kEnvVar);
+    if (dir.isEmpty()) return nullptr;
+    qInfo().noquote() << "DebugWavRecorder: dumping session audio to" << dir;
+    return std::make_unique<DebugWavRecorder>(dir);
+}
+
+DebugWavRecorder::DebugWavRecorder(QString dir) : dir_(std::move(dir)) {}
+
+DebugWavRecorder::~DebugWavRecorder() { finish(); }
+
+void DebugWavRecorder::begin(int sampleRate) {
+    finish();
+    if (!QDir().mkpath(dir_)) {
+        qWarning().noquote() << "DebugWavRecorder: cannot create" << dir_;
+        return;
+    }
+    file_.setFileName(dir_ + QLatin1Char('/') +
+                      QUuid::createUuid().toString(QUuid::WithoutBraces) +
+                      QStringLiteral(".wav"));
+    if (!logging::openPrivate(file_, QIODevice::WriteOnly | QIODevice::Truncate)) {
+        qWarning().noquote() << "DebugWavRecorder: cannot open" << file_.fileName()
+                             << "—" << file_.errorString();
+        return;
+    }
+    dataBytes_ = 0;
+    sampleRate_ = sampleRate;
+    file_.write(wav::header(0, sampleRate_)); // placeholder, patched in finish()
+}
+
+void DebugWavRecorder::append(const QByteArray &pcm) {
+    if (!file_.isOpen()) return;
+    file_.write(pcm);
+    dataBytes_ += static_cast<quint32>(pcm.size());
+}
+
+void DebugWavRecorder::finish() {
+    if (!file_.isOpen()) return;
+    file_.seek(0);
+    file_.write(wav::header(dataBytes_, sampleRate_));
+    file_.close();
+    qInfo().noquote() << "DebugWavRecorder: wrote" << file_.fileName()
+                      << "(" << dataBytes_ << "bytes of PCM )";
+    pruneDirectory();
+}
+
+void DebugWavRecorder::pruneDirectory() const {
+    // Newest first; keep accumulating until over budget, delete the rest.
+    const auto entries = QDir(dir_).entryInfoList({QStringLiteral("*.wav")},
+                                                  QDir::Files, QDir::Time);
+    qint64 total = 0;
+    for (const QFileInfo &fi : entries) {
+        total += fi.size();
+        if (total <= kMaxDirBytes || fi.filePath() == file_.fileName()) continue;
+        QFile::remove(fi.filePath());
+    }
+}
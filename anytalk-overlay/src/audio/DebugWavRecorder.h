@@ -0,0 +1,33 @@
+#pragma once
+#include <QByteArray>
+#include <QFile>
+#include <QString>
+#include <memory>
+
+/// Opt-in dump of the exact PCM forwarded to the ASR backend: one
+/// `<dir>/<uuid>.wav` (mono S16LE at the session rate, 0600) per session. Enabled only by
+/// ANYTALK_DEBUG_WAV_DIR — never implicitly. Driven from AsrController on
+/// the main thread; the capture thread never touches the file.
+class DebugWavRecorder {
+public:
+    /// nullptr unless ANYTALK_DEBUG_WAV_DIR is set to a non-empty path.
+    static std::unique_ptr<DebugWavRecorder> fromEnvironment();
+
+    explicit DebugWavRecorder(QString dir);
+    ~DebugWavRecorder();
+
+    /// Open a new dump file. Finishes the previous one if still open.
+    void begin(int sampleRate);
+    void append(const QByteArray &pcm);
+    /// Patch the RIFF sizes, close the file and prune old dumps. No-op when
+    /// no session is open.
+    void finish();
+
+private:
+    void pruneDirectory() const;
+
+    QString dir_;
+    QFile file_;
+    quint32 dataBytes_ = 0;
+    int sampleRate_ = 0;
+};
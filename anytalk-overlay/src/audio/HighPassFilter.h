@@ -0,0 +1,37 @@
+#pragma once
+#include <QByteArray>
+#include <QtEndian>
+#include <algorithm>
+#include <cmath>
+#include <numbers>
+
+/// One-pole high-pass for S16LE mono PCM: y[n] = a·(y[n-1] + x[n] − x[n-1]).
+/// Removes DC offset and sub-voice rumble (cheap USB mics, desk thumps)
+/// that otherwise eats into the ASR model's dynamic range. State persists
+/// across chunks, so one instance must see the stream in order.
+class HighPassFilter {
+public:
+    HighPassFilter(double cutoffHz, int sampleRate) {
+        const double rc = 1.0 / (2.0 * std::numbers::pi * cutoffHz);
+        const double dt = 1.0 / sampleRate;
+        a_ = rc / (rc + dt);
+    }
+
+    void process(QByteArray &pcm16le) {
+        char *p = pcm16le.data();
+        const qsizetype n = pcm16le.size() / 2;
+        for (qsizetype i = 0; i < n; ++i) {
+            const double x = qFromLittleEndian<qint16>(p + i * 2);
+            const double y = a_ * (prevOut_ + x - prevIn_);
+            prevIn_ = x;
+            prevOut_ = y;
+            qToLittleEndian(static_cast<qint16>(std::clamp(std::lround(y), -32768L, 32767L)),
+                            p + i * 2);
+        }
+    }
+
+private:
+    double a_ = 1.0;
+    double prevIn_ = 0.0;
+    double prevOut_ = 0.0;
+};
@@ -0,0 +1,17 @@
+#pragma once
+
+/// The one description of the PCM we produce and declare: AudioCapture
+/// opens the stream with it and the backend builds its request from it, so
+/// the bytes sent and the format announced can't drift apart. Interleaved
+/// little-endian signed integer samples.
+struct PcmFormat {
+    int sampleRate = 16000;
+    int bitsPerSample = 16;
+    int channels = 1;
+
+    int bytesPerFrame() const { return bitsPerSample / 8 * channels; }
+    /// Bytes covering `ms` of audio.
+    int bytesFor(int ms) const { return sampleRate / 1000 * ms * bytesPerFrame(); }
+
+    bool operator==(const PcmFormat &) const = default;
+};
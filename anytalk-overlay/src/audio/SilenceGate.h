@@ -0,0 +1,72 @@
+#pragma once
+#include <QByteArray>
+#include <QList>
+#include <QtEndian>
+#include <cmath>
+
+/// Drops the silence (and hotkey clack) at the start of a session: holds
+/// chunks back until the level stays above `thresholdDbfs` for `minSpeechMs`,
+/// then releases the last `preRollMs` of held audio — so the onset of the
+/// first phoneme isn't clipped — and passes everything after untouched.
+/// One instance per session; reset() re-arms it. S16LE mono.
+class SilenceGate {
+public:
+    SilenceGate(double thresholdDbfs, int minSpeechMs, int preRollMs, int bytesPerMs)
+        : threshold_(thresholdDbfs),
+          minSpeechBytes_(minSpeechMs * bytesPerMs),
+          preRollBytes_(preRollMs * bytesPerMs) {}
+
+    void reset() {
+        open_ = false;
+        held_.clear();
+        heldBytes_ = 0;
+        loudBytes_ = 0;
+    }
+
+    bool isOpen() const { return open_; }
+
+    /// Returns what to forward for `chunk`: nothing while gated, the
+    /// pre-roll plus `chunk` when the gate opens, `chunk` afterwards.
+    QByteArray feed(const QByteArray &chunk) {
+        if (open_) return chunk;
+        held_.append(chunk);
+        heldBytes_ += chunk.size();
+        loudBytes_ = dbfs(chunk) >= threshold_ ? loudBytes_ + chunk.size() : 0;
+        if (loudBytes_ >= minSpeechBytes_) {
+            open_ = true;
+            QByteArray out;
+            const qsizetype keep = loudBytes_ + preRollBytes_;
+            for (const auto &c : held_) out.append(c);
+            held_.clear();
+            heldBytes_ = 0;
+            return out.size() > keep ? out.right(keep) : out;
+        }
+        // Only pre-roll plus the current loud run can ever be released.
+        while (!held_.isEmpty() && heldBytes_ - held_.first().size() >= loudBytes_ + preRollBytes_) {
+            heldBytes_ -= held_.first().size();
+            held_.removeFirst();
+        }
+        return {};
+    }
+
+private:
+    static double dbfs(const QByteArray &pcm16le) {
+        const qsizetype n = pcm16le.size() / 2;
+        if (n == 0) return -120.0;
+        double sumSq = 0.0;
+        for (qsizetype i = 0; i < n; ++i) {
+            const double v = qFromLittleEndian<qint16>(pcm16le.constData() + i * 2) / 32768.0;
+            sumSq += v * v;
+        }
+        const double rms = std::sqrt(sumSq / static_cast<double>(n));
+        return rms > 0.0 ? 20.0 * std::log10(rms) : -120.0;
+    }
+
+    double threshold_;
+    qsizetype minSpeechBytes_;
+    qsizetype preRollBytes_;
+    bool open_ = false;
+    QList<QByteArray> held_;
+    qsizetype heldBytes_ = 0;
+    qsizetype loudBytes_ = 0;  // trailing run of chunks at/above threshold
+};
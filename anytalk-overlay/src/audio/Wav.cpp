@@ -0,0 +1,132 @@
+#include "Wav.h"
+
+#include <QFile>
+#include <QtEndian>
+
+namespace wav {
+
+namespace {
+constexpr quint16 kFormatPcm = 1;
+constexpr quint16 kFormatExtensible = 0xFFFE;
+
+quint16 u16At(const QByteArray &b, qsizetype off) {
+    return qFromLittleEndian<quint16>(b.constData() + off);
+}
+quint32 u32At(const QByteArray &b, qsizetype off) {
+    return qFromLittleEndian<quint32>(b.constData() + off);
+}
+
+Parsed fail(const QString &why) {
+    Parsed p;
+    p.error = why;
+    return p;
+}
+} // namespace
+
+QByteArray header(quint32 dataBytes, int sampleRate, int channels) {
+    const auto rate = static_cast<quint32>(sampleRate);
+    const auto ch = static_cast<quint16>(channels);
+    QByteArray h;
+    h.reserve(44);
+    auto u16 = [&h](quint16 v) {
+        char b[2];
+        qToLittleEndian(v, b);
+        h.append(b, 2);
+    };
+    auto u32 = [&h](quint32 v) {
+        char b[4];
+        qToLittleEndian(v, b);
+        h.append(b, 4);
+    };
+    h.append("RIFF");
+    u32(36 + dataBytes);
+    h.append("WAVE");
+    h.append("fmt ");
+    u32(16);            // PCM fmt chunk size
+    u16(kFormatPcm);
+    u16(ch);
+    u32(rate);
+    u32(rate * ch * 2); // byte rate
+    u16(ch * 2);        // block align
+    u16(16);            // bits per sample
+    h.append("data");
+    u32(dataBytes);
+    return h;
+}
+
+Parsed parse(const QByteArray &file) {
+    if (file.size() < 12 || !file.startsWith("RIFF") || file.mid(8, 4) != "WAVE") {
+        return fail(QStringLiteral("not a RIFF/WAVE file"));
+    }
+
+    Parsed p;
+    bool haveFmt = false;
+    qsizetype off = 12;
+    while (off + 8 <= file.size()) {
+        const QByteArray id = file.mid(off, 4);
+        const quint32 size = u32At(file, off + 4);
+        const qsizetype body = off + 8;
+        const qsizetype avail = file.size() - body;
+
+        if (id == "fmt ") {
+            if (size < 16 || avail < 16) return fail(QStringLiteral("truncated fmt chunk"));
+            const quint16 format = u16At(file, body);
+            p.channels = u16At(file, body + 2);
+            p.sampleRate = static_cast<int>(u32At(file, body + 4));
+            const quint16 bits = u16At(file, body + 14);
+            if (format != kFormatPcm && format != kFormatExtensible) {
+                return fail(QStringLiteral("unsupported encoding %1 (need PCM)").arg(format));
+            }
+            if (bits != 16) {
+                return fail(QStringLiteral("unsupported %1-bit samples (need 16)").arg(bits));
+            }
+            if (p.channels < 1) return fail(QStringLiteral("zero channels"));
+            haveFmt = true;
+        } else if (id == "data") {
+            if (!haveFmt) return fail(QStringLiteral("data chunk before fmt chunk"));
+            // 0 / 0xFFFFFFFF are "still recording" placeholders; anything
+            // past EOF means the writer died before patching the header.
+            const qsizetype len = (size == 0 || size > static_cast<quint64>(avail))
+                                      ? avail
+                                      : static_cast<qsizetype>(size);
+            const qsizetype frame = 2 * p.channels;
+            p.pcm = file.mid(body, len - len % frame);
+            p.ok = true;
+            return p;
+        }
+        // Chunks are word-aligned: odd sizes carry one pad byte.
+        off = body + static_cast<qsizetype>(size) + (size & 1);
+    }
+    return fail(haveFmt ? QStringLiteral("no data chunk") : QStringLiteral("no fmt chunk"));
+}
+
+Parsed readMono(const QString &path, int sampleRate) {
+    QFile f(path);
+    if (!f.open(QIODevice::ReadOnly)) {
+        return fail(QStringLiteral("cannot open %1: %2").arg(path, f.errorString()));
+    }
+    Parsed p = parse(f.readAll());
+    if (!p.ok) return p;
+    if (p.sampleRate != sampleRate) {
+        return fail(QStringLiteral("%1 Hz audio, need %2 Hz (convert with "
+                                   "`ffmpeg -i in.wav -ar %2 -ac 1 out.wav`)")
+                        .arg(p.sampleRate)
+                        .arg(sampleRate));
+    }
+    if (p.channels == 1) return p;
+
+    const qsizetype frames = p.pcm.size() / (2 * p.channels);
+    QByteArray mono(frames * 2, Qt::Uninitialized);
+    for (qsizetype i = 0; i < frames; ++i) {
+        int sum = 0;
+        for (int c = 0; c < p.channels; ++c) {
+            sum += qFromLittleEndian<qint16>(p.pcm.constData() + (i * p.channels + c) * 2);
+        }
+        qToLittleEndian(static_cast<qint16>(sum / p.channels), mono.data() + i * 2);
+    }
+    p.pcm = mono;
+    p.channels = 1;
+    return p;
+}
+
+} // namespace wav
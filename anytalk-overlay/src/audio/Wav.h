@@ -0,0 +1,31 @@
+#pragma once
+#include <QByteArray>
+#include <QString>
+
+/// Minimal RIFF/WAVE codec for 16-bit PCM — just enough for the debug dump
+/// and file-fed audio. No dependency, no resampler: the live path gets its
+/// rate from the sound server, so files at any other rate are rejected.
+namespace wav {
+
+/// 44-byte canonical header for `dataBytes` of S16LE PCM.
+QByteArray header(quint32 dataBytes, int sampleRate, int channels = 1);
+
+struct Parsed {
+    bool ok = false;
+    QString error;          // when !ok
+    int sampleRate = 0;
+    int channels = 0;       // as stored in the file
+    QByteArray pcm;         // S16LE, interleaved as stored
+};
+
+/// Parse a whole .wav image. Accepts PCM / WAVE_FORMAT_EXTENSIBLE at
+/// 16 bits. A data chunk that claims more bytes than the file holds (a
+/// recorder killed before patching the header) is clamped to what's there.
+Parsed parse(const QByteArray &file);
+
+/// Read `path` and normalize to what AsrBackend::pushPcm expects: mono
+/// S16LE at `sampleRate`. Multi-channel input is averaged down to mono;
+/// any other sample rate is an error.
+Parsed readMono(const QString &path, int sampleRate);
+
+} // namespace wav
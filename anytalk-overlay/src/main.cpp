@@ -1,17 +1,29 @@
 #include "AsrController.h"
+#include "Bench.h"
 #include "Config.h"
+#include "Logging.h"
 #include "OverlayService.h"
 #include "OverlayState.h"
 #include "OverlayWindow.h"
+#include "SelfCheck.h"
 #include "SettingsDialog.h"
+#include "TranscriptLog.h"
+#include "asr/VolcengineFrameLog.h"
 
 #include <QApplication>
 #include <QCommandLineParser>
 #include <QDebug>
+#include <QFile>
+#include <QProcess>
 #include <QSocketNotifier>
+#include <QTextStream>
 #include <QTimer>
 
+#include <algorithm>
 #include <csignal>
+#include <functional>
+#include <memory>
+#include <utility>
 #include <cstdlib>
 #include <sys/socket.h>
 #include <unistd.h>
@@ -24,7 +36,7 @@ namespace {
 bool runSettingsDialog(AsrController &asr) {
     SettingsDialog dlg(OverlayConfig::load());
     if (dlg.exec() != QDialog::Accepted) return false;
-    if (!asr.applyConfig(dlg.config())) {
+    if (!asr.applyConfig(dlg.config().resolved())) {
         qWarning() << "anytalk-overlay: applyConfig rejected — controller not idle. "
                       "Saved to file; restart or stop recording first to apply.";
     }
@@ -48,11 +60,62 @@ bool runSettingsDialog(AsrController &asr) {
 //   own, which makes the PA daemon see EOF and release the stream cleanly
 //   in microseconds. Layer-shell surface, D-Bus name, and capture thread
 //   are all torn down by the kernel — no cleanup races.
+//
+//   The one opt-in exception is a session in flight: dropping it loses
+//   whatever the server hasn't finalized yet. With [Overlay] ShutdownGrace
+//   set, drainOnSignal gets one chance to stop the session gracefully (see
+//   main()); a second signal, or the drain deadline, still ends in _Exit.
 int sigPipe[2] = {-1, -1};
+std::function<void()> announceShutdown;  // runs once, before drain or exit
+std::function<bool()> drainOnSignal;  // true = draining, don't exit yet
+std::function<void()> toggleOnSignal;  // SIGUSR2
+
+void signalHandler(int sig) {
+    const char byte = static_cast<char>(sig);
+    [[maybe_unused]] auto _ = ::write(sigPipe[1], &byte, 1);
+}
 
-void signalHandler(int) {
-    const char one = 1;
-    [[maybe_unused]] auto _ = ::write(sigPipe[1], &one, 1);
+/// Deliver a signal-initiated session's text (`[Overlay] SignalOutput`):
+/// `file:<path>` appends a line, anything else is a program plus arguments
+/// (split like a shell would, but never run through one) that gets the
+/// text on stdin (`wtype -`, `xdotool type --file -`). `done` runs once the
+/// text is written or the program has exited, successfully or not.
+void deliverToSignalOutput(const QString &spec, const QString &text, QObject *parent,
+                           const std::function<void()> &done) {
+    if (spec.startsWith(QLatin1String("file:"))) {
+        QFile f(spec.mid(5));
+        if (f.open(QIODevice::WriteOnly | QIODevice::Append | QIODevice::Text)) {
+            f.write(text.toUtf8() + '\n');
+        } else {
+            qWarning() << "anytalk-overlay: SignalOutput" << f.fileName() << f.errorString();
+        }
+        done();
+        return;
+    }
+    QStringList argv = QProcess::splitCommand(spec);
+    if (argv.isEmpty()) {
+        qWarning() << "anytalk-overlay: SignalOutput has no program";
+        done();
+        return;
+    }
+    auto *proc = new QProcess(parent);
+    QObject::connect(proc, &QProcess::finished, proc, [proc, done](int code) {
+        if (code != 0) qWarning() << "anytalk-overlay: SignalOutput command exited with" << code;
+        proc->deleteLater();
+        done();
+    });
+    QObject::connect(proc, &QProcess::errorOccurred, proc,
+                     [proc, done](QProcess::ProcessError err) {
+        qWarning() << "anytalk-overlay: SignalOutput command failed:" << proc->errorString();
+        // Any other error is followed by finished().
+        if (err != QProcess::FailedToStart) return;
+        proc->deleteLater();
+        done();
+    });
+    const QString program = argv.takeFirst();
+    proc->start(program, argv);
+    proc->write(text.toUtf8());
+    proc->closeWriteChannel();
 }
 
 void installCleanShutdownHandlers(QApplication &app) {
@@ -61,11 +124,18 @@ void installCleanShutdownHandlers(QApplication &app) {
     QObject::connect(notifier, &QSocketNotifier::activated, &app, []() {
         char buf;
         [[maybe_unused]] auto _ = ::read(sigPipe[0], &buf, 1);
+        if (buf == SIGUSR2) {
+            if (toggleOnSignal) toggleOnSignal();
+            return;
+        }
+        if (announceShutdown) std::exchange(announceShutdown, nullptr)();
+        if (drainOnSignal && std::exchange(drainOnSignal, nullptr)()) return;
         ::_Exit(0);
     });
     std::signal(SIGTERM, signalHandler);
     std::signal(SIGINT, signalHandler);
     std::signal(SIGHUP, signalHandler);
+    std::signal(SIGUSR2, signalHandler);
 }
 
 } // namespace
@@ -91,26 +161,145 @@ int main(int argc, char **argv) {
     QCommandLineOption settingsOption(QStringLiteral("settings"),
                                        QStringLiteral("Open the settings dialog and exit."));
     parser.addOption(settingsOption);
+    QCommandLineOption replayOption(
+        QStringLiteral("replay"),
+        QStringLiteral("Re-parse a server frame log (ANYTALK_DEBUG_FRAMES_DIR) and exit."),
+        QStringLiteral("file"));
+    parser.addOption(replayOption);
+    QCommandLineOption expectOption(
+        QStringLiteral("expect"),
+        QStringLiteral("With --replay: compare the output to a golden file; exit 1 at the first difference."),
+        QStringLiteral("golden"));
+    parser.addOption(expectOption);
+    QCommandLineOption simulateAudioOption(
+        QStringLiteral("simulate-audio"),
+        QStringLiteral("Use a WAV file (at the backend rate, normally 16 kHz) as the microphone (real-time, then silence)."),
+        QStringLiteral("wav"));
+    parser.addOption(simulateAudioOption);
+    QCommandLineOption benchOption(
+        QStringLiteral("bench"),
+        QStringLiteral("With --simulate-audio: run N sessions on the file, print latency percentiles as JSON and exit."),
+        QStringLiteral("n"));
+    parser.addOption(benchOption);
+    QCommandLineOption testModeOption(
+        QStringLiteral("test-mode"),
+        QStringLiteral("Enable the InjectTranscript D-Bus method for automated UI tests."));
+    parser.addOption(testModeOption);
+    QCommandLineOption replaceOption(
+        QStringLiteral("replace"),
+        QStringLiteral("Take over the D-Bus name from a running overlay, which then exits."));
+    parser.addOption(replaceOption);
+    QCommandLineOption configOption(
+        QStringLiteral("config"),
+        QStringLiteral("Config file to use instead of ~/.config/fcitx5/conf/anytalk.conf "
+                       "(env: ANYTALK_CONFIG)."),
+        QStringLiteral("path"));
+    parser.addOption(configOption);
+    QCommandLineOption logLevelOption(QStringLiteral("log-level"),
+                                      QStringLiteral("debug | info | warning (default info)."),
+                                      QStringLiteral("level"));
+    parser.addOption(logLevelOption);
+    QCommandLineOption logFileOption(
+        QStringLiteral("log-file"),
+        QStringLiteral("stderr | journal | <path> (env: ANYTALK_LOG_FILE)."),
+        QStringLiteral("sink"));
+    parser.addOption(logFileOption);
+    QCommandLineOption logFormatOption(QStringLiteral("log-format"),
+                                       QStringLiteral("text | json (env: ANYTALK_LOG_FORMAT)."),
+                                       QStringLiteral("format"));
+    parser.addOption(logFormatOption);
+    QCommandLineOption printConfigOption(
+        QStringLiteral("print-config"),
+        QStringLiteral("Print the effective config (secrets redacted) and exit."));
+    parser.addOption(printConfigOption);
+    QCommandLineOption checkOption(
+        QStringLiteral("check"),
+        QStringLiteral("Check config, audio source and ASR handshake; print PASS/FAIL and exit."));
+    parser.addOption(checkOption);
     parser.process(app);
 
+    // CLI first, env as fallback; before anything else logs or reads config.
+    logging::Format logFormat = logging::Format::Text;
+    const QString logFormatName = parser.isSet(logFormatOption)
+                                      ? parser.value(logFormatOption)
+                                      : qEnvironmentVariable("ANYTALK_LOG_FORMAT");
+    const bool logFormatOk =
+        logFormatName.isEmpty() || logging::formatFromString(logFormatName, &logFormat);
+    logging::install(parser.isSet(logFileOption) ? parser.value(logFileOption)
+                                                 : qEnvironmentVariable("ANYTALK_LOG_FILE"),
+                     logFormat);
+    if (!logFormatOk) {
+        qWarning() << "anytalk-overlay: unknown log format" << logFormatName << "— using text";
+    }
+    if (parser.isSet(logLevelOption) && !logging::setLevel(parser.value(logLevelOption))) {
+        qWarning() << "anytalk-overlay: unknown --log-level" << parser.value(logLevelOption);
+    }
+    if (parser.isSet(configOption)) OverlayConfig::setConfigFilePath(parser.value(configOption));
+
+    if (parser.isSet(printConfigOption)) {
+        const OverlayConfig cfg = OverlayConfig::load().resolved();
+        QTextStream out(stdout);
+        out << "# " << OverlayConfig::configFilePath();
+        if (!cfg.activeProfile.isEmpty()) out << " (profile " << cfg.activeProfile << ")";
+        out << "\n" << cfg.toIni(/*redactSecrets=*/true);
+        return 0;
+    }
+
+    // One-shot diagnostics: no window, no bus name, so it can run next to
+    // a live overlay.
+    if (parser.isSet(checkOption)) {
+        QTextStream out(stdout);
+        return selfcheck::run(out);
+    }
+
+    // Offline: no window, no D-Bus, no audio.
+    if (parser.isSet(replayOption)) {
+        QTextStream out(stdout);
+        if (parser.isSet(expectOption)) {
+            return volcengine::checkFrameLog(parser.value(replayOption),
+                                             parser.value(expectOption), out);
+        }
+        return volcengine::replayFrameLog(parser.value(replayOption), out);
+    }
+
     OverlayWindow overlay;
 
     AsrController asr;
-    OverlayConfig cfg = OverlayConfig::load();
-    if (!asr.applyConfig(cfg)) {
+    if (parser.isSet(simulateAudioOption)) {
+        asr.setSimulatedAudio(parser.value(simulateAudioOption));
+    }
+    OverlayConfig cfg = OverlayConfig::load().resolved();
+    const bool asrReady = asr.applyConfig(cfg);
+    if (!asrReady) {
         qWarning() << "anytalk-overlay: ASR backend not configured. The first F2 will "
                       "open the settings dialog.";
     }
 
+    // Latency runs over the simulated source: no window, no bus name.
+    if (parser.isSet(benchOption)) {
+        if (!parser.isSet(simulateAudioOption)) {
+            qWarning() << "anytalk-overlay: --bench needs --simulate-audio";
+            return 1;
+        }
+        if (!asrReady) return 1;
+        QTextStream out(stdout);
+        return bench::run(asr, parser.value(simulateAudioOption),
+                          std::max(1, parser.value(benchOption).toInt()), out);
+    }
+
     // CLI-driven settings: launch dialog and exit.
     if (parser.isSet(settingsOption)) {
         return runSettingsDialog(asr) ? 0 : 1;
     }
 
     OverlayService service(&overlay, &asr);
-    if (!service.registerOnBus()) {
+    if (parser.isSet(testModeOption)) {
+        qWarning() << "anytalk-overlay: test mode — InjectTranscript is enabled";
+        service.setTestMode(true);
+    }
+    if (!service.registerOnBus(parser.isSet(replaceOption))) {
         qWarning() << "anytalk-overlay: D-Bus registration failed; another "
-                      "instance may already own the name.";
+                      "instance may already own the name (use --replace to take over).";
         return 1;
     }
 
@@ -140,10 +329,64 @@ int main(int argc, char **argv) {
                      &OverlayService::TranscriptPartial);
     QObject::connect(&asr, &AsrController::transcriptFinal, &service,
                      &OverlayService::TranscriptFinal);
+    QObject::connect(&asr, &AsrController::transcriptFinalMeta, &service,
+                     &OverlayService::TranscriptFinalMeta);
     QObject::connect(&asr, &AsrController::errorOccurred, &service,
                      &OverlayService::ErrorOccurred);
+    QObject::connect(&asr, &AsrController::warningOccurred, &service,
+                     &OverlayService::Warning);
+    QObject::connect(&asr, &AsrController::sessionStats, &service,
+                     &OverlayService::SessionStats);
+    // Opt-in history; connected ahead of delivery so the line is written
+    // before the addon can Acknowledge and let us exit.
+    const std::unique_ptr<TranscriptLog> transcriptLog = TranscriptLog::fromConfig(cfg);
+    if (transcriptLog) {
+        QObject::connect(&asr, &AsrController::commitText, &app,
+                         [log = transcriptLog.get()](const QString &text) { log->append(text); });
+    }
+    // Sessions toggled by SIGUSR2 (no addon involved) deliver their text
+    // to [Overlay] SignalOutput instead of CommitText, and expect no
+    // Acknowledge: once the session is over and its text is out, the
+    // process exits as it would after an ack.
+    bool signalSession = false;
+    int signalDeliveries = 0;  // SignalOutput writes still in flight
+    const QString signalOutput = cfg.str(QStringLiteral("Overlay"), QStringLiteral("SignalOutput"));
     QObject::connect(&asr, &AsrController::commitText, &service,
-                     &OverlayService::CommitText);
+                     [&service, &app, &asr, &signalSession, &signalDeliveries,
+                      signalOutput](const QString &text) {
+        if (!signalSession) {
+            emit service.CommitText(text);
+            return;
+        }
+        ++signalDeliveries;
+        deliverToSignalOutput(signalOutput, text, &app,
+                              [&asr, &signalSession, &signalDeliveries]() {
+            if (--signalDeliveries == 0 && !signalSession && !asr.isRecording()) {
+                QApplication::quit();
+            }
+        });
+    });
+    QObject::connect(&asr, &AsrController::stateChanged, &app,
+                     [&signalSession, &signalDeliveries](const QString &s) {
+        if (s != state::Idle || !std::exchange(signalSession, false)) return;
+        // Otherwise the last delivery's completion quits.
+        if (signalDeliveries == 0) QApplication::quit();
+    });
+    toggleOnSignal = [&asr, &signalSession, signalOutput]() {
+        if (asr.isRecording()) {
+            // Only our own session: one started over D-Bus belongs to the
+            // addon and its focused input context.
+            if (signalSession) asr.stopRecording();
+            else qInfo() << "anytalk-overlay: SIGUSR2 ignored — session owned by a client";
+            return;
+        }
+        if (signalOutput.isEmpty()) {
+            qWarning() << "anytalk-overlay: SIGUSR2 ignored — [Overlay] SignalOutput is not set";
+            return;
+        }
+        signalSession = true;
+        asr.startRecording();
+    };
     QObject::connect(&asr, &AsrController::cancelled, &service,
                      &OverlayService::Cancelled);
 
@@ -157,7 +400,8 @@ int main(int argc, char **argv) {
     // Exit paths:
     //   1. CommitText emitted → wait up to 5 s for the addon's
     //      Acknowledge() callback, then QApplication::quit() (clean dtors —
-    //      PA stream, layer-shell surface, D-Bus name).
+    //      PA stream, layer-shell surface, D-Bus name). An ack that lands
+    //      while a session is still recording (CommitPartial) doesn't exit.
     //   2. Ack timeout (5 s) → addon hung. Force _Exit(0).
     //   3. Esc / CancelRecording → cancelEscape fires; _Exit immediately.
     //   4. AsrController cancelled (no commit) → quit() (clean dtors).
@@ -165,12 +409,20 @@ int main(int argc, char **argv) {
     //      this the overlay sat in error indefinitely and held the D-Bus
     //      name, blocking the next F2.
     //
-    // No idle watchdog. The earlier 3 s timer killed the process before
-    // dbus-daemon could deliver the queued auto-activation method call —
-    // cold startup is ~2.9 s. We accept the small risk that an
-    // accidental introspect or stray method poke leaves a long-lived
-    // idle overlay; in practice nothing on the bus calls our service
-    // except the addon, and the addon only calls during a real F2.
+    //   6. `[Overlay] IdleExit` seconds with no session → _Exit(0), like
+    //      2, 3 and 5: nothing is in flight, so the destructors have
+    //      nothing to save. Off by default and never below 30 s: the
+    //      earlier 3 s idle timer killed the process before dbus-daemon
+    //      could deliver the queued auto-activation method call — cold
+    //      startup is ~2.9 s. Opt-in for users who poke the service from
+    //      scripts and would rather not leave an idle overlay (and its
+    //      layer surface) behind.
+    //   7. A SIGUSR2-started session ends → quit() once its text has
+    //      gone to [Overlay] SignalOutput; nobody sends an Acknowledge.
+    //   8. SIGTERM/SIGINT → _Exit(0) (see installCleanShutdownHandlers).
+    //      With `[Overlay] ShutdownGrace` (opt-in, ≤ 10 s) a live session
+    //      is stopped first and exits via 1, 4 or 7; a second signal or the
+    //      deadline still forces _Exit(0).
 
     // Error display + exit. 3 s is enough for the user to read the error
     // tooltip before the overlay disappears.
@@ -186,6 +438,30 @@ int main(int argc, char **argv) {
         else errorTimer->stop();
     });
 
+    // [Overlay] IdleExit: s with no session before quitting; 0 = never,
+    // else at least 30.
+    if (const int idleSecs =
+            cfg.intIn(QStringLiteral("Overlay"), QStringLiteral("IdleExit"), 0, 0, 86400);
+        idleSecs > 0) {
+        auto *idleTimer = new QTimer(&app);
+        idleTimer->setSingleShot(true);
+        idleTimer->setInterval(std::max(idleSecs, 30) * 1000);
+        QObject::connect(idleTimer, &QTimer::timeout, &app, [&asr, idleTimer]() {
+            // A modal SettingsDialog runs a nested loop; don't yank it.
+            if (asr.isRecording() || QApplication::activeModalWidget()) {
+                idleTimer->start();
+                return;
+            }
+            qInfo() << "anytalk-overlay: idle timeout — exiting";
+            ::_Exit(0);
+        });
+        // Every session transition counts as activity; the countdown only
+        // really matters once we are back in idle.
+        QObject::connect(&asr, &AsrController::stateChanged, idleTimer,
+                         [idleTimer](const QString &) { idleTimer->start(); });
+        idleTimer->start();
+    }
+
     auto *ackTimer = new QTimer(&app);
     ackTimer->setSingleShot(true);
     QObject::connect(ackTimer, &QTimer::timeout, &app, []() {
@@ -193,25 +469,68 @@ int main(int argc, char **argv) {
         ::_Exit(0);
     });
     QObject::connect(&asr, &AsrController::commitText, ackTimer,
-                     [ackTimer](const QString &) {
+                     [ackTimer, &signalSession](const QString &) {
+        if (signalSession) return;  // nobody will acknowledge
         // 5 s is generous: addon's commitString + busctl call is sub-ms in
         // practice. Anything longer is the addon being broken; user can
         // press Esc to bypass. Past that, Force-exit so the next F2 isn't
         // blocked by a stale bus name.
         ackTimer->start(5000);
     });
-    QObject::connect(&service, &OverlayService::ackReceived, &app, []() {
+    QObject::connect(&service, &OverlayService::ackReceived, &app, [&asr, ackTimer]() {
+        ackTimer->stop();
+        // CommitPartial or a spoken "commit" mid-session: the addon acked,
+        // keep listening.
+        if (asr.isRecording()) return;
         QApplication::quit();
     });
     QObject::connect(&service, &OverlayService::cancelEscape, &app, []() {
         // User abort: don't bother with destructors, just go.
         ::_Exit(0);
     });
+    QObject::connect(&service, &OverlayService::nameLost, &app, []() {
+        // Replaced (--replace): the new instance owns the addon's traffic
+        // now. Same reasoning as cancel — drop the mic and go.
+        ::_Exit(0);
+    });
     QObject::connect(&asr, &AsrController::cancelled, &app, []() {
         // No commit means addon never gets a CommitText, so it'll never
         // call Acknowledge. Quit on our own.
         QApplication::quit();
     });
 
+    // SIGTERM/SIGINT mid-session: stop the mic and let the backend drain
+    // its finals, so the normal commit → Acknowledge → quit path runs.
+    // [Overlay] ShutdownGrace bounds the wait (s, default 0 = exit at once).
+    // Capped far below systemd's 90 s stop timeout, which would SIGKILL us.
+    const int graceSecs =
+        cfg.intIn(QStringLiteral("Overlay"), QStringLiteral("ShutdownGrace"), 0, 0, 10);
+    announceShutdown = [&service]() {
+        // Lets the addon / status bar say "overlay stopped" instead of
+        // seeing the name vanish. libdbus writes on send, so the _Exit
+        // that may follow doesn't lose it.
+        emit service.Warning(QStringLiteral("shutting_down"));
+    };
+    drainOnSignal = [&asr, &app, graceSecs]() {
+        if (graceSecs == 0 || !asr.isRecording()) return false;
+        qInfo() << "anytalk-overlay: signal during a session — draining for up to"
+                << graceSecs << "s";
+        QTimer::singleShot(graceSecs * 1000, &app, []() {
+            qWarning() << "anytalk-overlay: shutdown grace expired — force exit";
+            ::_Exit(0);
+        });
+        // Nothing to commit → no Acknowledge will come; leave as soon as
+        // the drain ends. (enterIdle emits commitText before the state.)
+        auto committed = std::make_shared<bool>(false);
+        QObject::connect(&asr, &AsrController::commitText, &app,
+                         [committed](const QString &) { *committed = true; });
+        QObject::connect(&asr, &AsrController::stateChanged, &app,
+                         [committed](const QString &s) {
+            if (s == state::Idle && !*committed) ::_Exit(0);
+        });
+        asr.stopRecording();
+        return true;
+    };
+
     return app.exec();
 }
@@ -0,0 +1,61 @@
+#include "PostProcess.h"
+
+#include <QTest>
+
+using postprocess::CommandMap;
+
+class TestCommandMap : public QObject {
+    Q_OBJECT
+private slots:
+    void emptyMapPassesThrough() {
+        const CommandMap map;
+        QCOMPARE(map.apply(QStringLiteral("你好")), QStringList{QStringLiteral("你好")});
+    }
+
+    void insertEatsSurroundingPunctuation() {
+        CommandMap map;
+        map.add(QStringLiteral("句号"), CommandMap::Action::Insert, QStringLiteral("。"));
+        QCOMPARE(map.apply(QStringLiteral("今天天气不错，句号。")),
+                 QStringList{QStringLiteral("今天天气不错。")});
+    }
+
+    void longestPhraseWins() {
+        CommandMap map;
+        map.add(QStringLiteral("段落"), CommandMap::Action::Newline);
+        map.add(QStringLiteral("新段落"), CommandMap::Action::Insert, QStringLiteral("¶"));
+        QCOMPARE(map.apply(QStringLiteral("开始新段落结束")),
+                 QStringList{QStringLiteral("开始¶结束")});
+        QCOMPARE(map.apply(QStringLiteral("开始段落结束")),
+                 QStringList{QStringLiteral("开始\n结束")});
+    }
+
+    void commitSplits() {
+        CommandMap map;
+        map.add(QStringLiteral("发送"), CommandMap::Action::Commit);
+        QCOMPARE(map.apply(QStringLiteral("你好发送世界")),
+                 (QStringList{QStringLiteral("你好"), QStringLiteral("世界")}));
+        QCOMPARE(map.apply(QStringLiteral("你好，发送。")),
+                 (QStringList{QStringLiteral("你好"), QString()}));
+    }
+
+    void latinMatchesWholeWordsOnly() {
+        CommandMap map;
+        map.add(QStringLiteral("commit"), CommandMap::Action::Commit);
+        map.add(QStringLiteral("period"), CommandMap::Action::Insert, QStringLiteral("."));
+        QCOMPARE(map.apply(QStringLiteral("the committee met periodically")),
+                 QStringList{QStringLiteral("the committee met periodically")});
+        QCOMPARE(map.apply(QStringLiteral("recommit")), QStringList{QStringLiteral("recommit")});
+        QCOMPARE(map.apply(QStringLiteral("done Period.")), QStringList{QStringLiteral("done .")});
+        QCOMPARE(map.apply(QStringLiteral("ok, COMMIT")),
+                 (QStringList{QStringLiteral("ok"), QString()}));
+    }
+
+    void latinNextToCjkIsAWordBoundary() {
+        CommandMap map;
+        map.add(QStringLiteral("period"), CommandMap::Action::Insert, QStringLiteral("。"));
+        QCOMPARE(map.apply(QStringLiteral("好的period")), QStringList{QStringLiteral("好的。")});
+    }
+};
+
+QTEST_APPLESS_MAIN(TestCommandMap)
+#include "tst_commandmap.moc"